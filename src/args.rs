@@ -32,6 +32,18 @@ pub struct Args {
     #[arg(short = 'U', long, action = ArgAction::SetTrue)]
     pub update_with_dependencies: bool,
 
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub upgrade: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub resume: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub abort: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub calibrate: bool,
+
     #[arg(short = 'd', long, action = ArgAction::SetTrue)]
     pub dependencies: bool,
 
@@ -68,6 +80,24 @@ pub struct Args {
     #[arg(short = 'S', long, action = ArgAction::SetTrue)]
     pub sync: bool,
 
+    #[arg(long, num_args = 2, value_names = ["NAME", "URL"])]
+    pub new: Option<Vec<String>>,
+
+    #[arg(long, value_name = "FORMAT", value_parser = ["dot", "json"])]
+    pub graph: Option<String>,
+
+    #[arg(long, num_args = 1..=2, value_names = ["PKG", "TARGET"])]
+    pub why: Option<Vec<String>>,
+
+    #[arg(long, value_name = "PKG")]
+    pub log: Option<String>,
+
+    #[arg(long, action = ArgAction::SetTrue, requires = "log", conflicts_with = "last")]
+    pub failed: bool,
+
+    #[arg(long, action = ArgAction::SetTrue, requires = "log")]
+    pub last: bool,
+
     // Generic flags
     #[arg(short = 'v', long, action = ArgAction::SetTrue)]
     pub verbose: bool,
@@ -78,6 +108,42 @@ pub struct Args {
     #[arg(short = 'f', long, action = ArgAction::SetTrue)]
     pub force: bool,
 
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub pretend: bool,
+
+    #[arg(short = 't', long, action = ArgAction::SetTrue)]
+    pub tree: bool,
+
+    #[arg(long, value_name = "N")]
+    pub depth: Option<usize>,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub all: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub rebuild_dependants: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub update_deps: bool,
+
+    #[arg(long, value_name = "N")]
+    pub parallel: Option<usize>,
+
+    #[arg(short = 'j', long, value_name = "N")]
+    pub make_jobs: Option<usize>,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub keep_going: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub sandbox: bool,
+
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub test: bool,
+
+    #[arg(long, value_name = "FD|PATH")]
+    pub events: Option<String>,
+
     // Positional arguments (packages)
     #[arg(value_name = "PACKAGE", num_args = 0.., value_delimiter = ' ')]
     pub packages: Vec<String>,
@@ -0,0 +1,122 @@
+// src/buildenv.rs
+//
+// responsible for layering build environment overrides and recording the result
+
+use crate::cmd::static_exec;
+use crate::config::CONFIG;
+use crate::flags::MAKE_JOBS;
+use crate::package::Package;
+use crate::paths::{ENVD, REPO, SETS};
+use crate::sets::expand_set;
+use crate::{erm, vpr};
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// always recorded, whether or not they're overridden
+const RECORDED: [&str; 5] = ["CFLAGS", "CXXFLAGS", "FFLAGS", "LDFLAGS", "MAKEFLAGS"];
+
+lazy_static! {
+    // sets with environment overrides, expanded once per run
+    static ref MEMBERS: HashMap<String, Vec<String>> = members();
+}
+
+fn members() -> HashMap<String, Vec<String>> {
+    CONFIG.env.set
+        .keys()
+        .filter(|set| {
+            let exists = SETS.join(set).exists();
+            if !exists { erm!("Ignoring environment for missing set '@{}'", set) }
+            exists
+        })
+        .map(|set| (set.clone(), expand_set(&format!("@{}", set), &Vec::new())))
+        .collect()
+}
+
+fn layer(vars: &mut Vec<(String, String)>, overrides: impl IntoIterator<Item = (String, String)>) {
+    // an overridden variable keeps its place, so later variables may still refer to it
+    for (k, v) in overrides {
+        match vars.iter_mut().find(|(key, _)| *key == k) {
+            Some(var) => var.1 = v,
+            None => vars.push((k, v)),
+        }
+    }
+}
+
+fn envd(name: &str) -> Vec<(String, String)> {
+    // $RIDHOME/env.d/<pkg>, with KEY=VALUE lines
+    let Ok(contents) = fs::read_to_string(ENVD.join(name)) else { return Vec::new() };
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let (k, v) = l.strip_prefix("export ").unwrap_or(l).split_once('=')?;
+            let v = v.trim();
+            let unquote = |q: char| v.strip_prefix(q).and_then(|v| v.strip_suffix(q));
+            let v = unquote('"').or_else(|| unquote('\'')).unwrap_or(v);
+            Some((k.trim().to_string(), v.to_string()))
+        })
+        .collect()
+}
+
+pub fn overrides(p: &Package) -> Vec<(String, String)> {
+    // global, then repo, then sets (alphabetically), then the package, then env.d, then -j
+    let env = &CONFIG.env;
+    let owned = |m: &BTreeMap<String, String>| m.iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>();
+    let mut vars = Vec::new();
+
+    layer(&mut vars, owned(&env.global));
+    if let Some(m) = env.repo.get(&*REPO) { layer(&mut vars, owned(m)) }
+
+    for (set, m) in env.set.iter() {
+        if MEMBERS.get(set).is_some_and(|names| names.contains(&p.name)) { layer(&mut vars, owned(m)) }
+    }
+
+    if let Some(m) = env.package.get(&p.name) { layer(&mut vars, owned(m)) }
+    layer(&mut vars, envd(&p.name));
+
+    if let Some(n) = *MAKE_JOBS.lock().unwrap() {
+        layer(&mut vars, [("MAKEFLAGS".to_string(), format!("-j{}", n)), ("NINJAJOBS".to_string(), n.to_string())]);
+    }
+
+    vars
+}
+
+fn escape(v: &str) -> String {
+    // values are double quoted, so they may refer to other variables
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('`', "\\`")
+}
+
+pub fn write(path: &Path, vars: &[(String, String)]) {
+    let contents: String = vars.iter().map(|(k, v)| format!("export {}=\"{}\"\n", k, escape(v))).collect();
+    if let Err(e) = fs::write(path, contents) {
+        erm!("Failed to write build environment to '{}': {}", path.display(), e)
+    }
+}
+
+pub fn effective(path: &Path, vars: &[(String, String)]) -> BTreeMap<String, String> {
+    // sources the env files the way mint does and reads back the result
+    let mut keys: Vec<&str> = RECORDED.to_vec();
+    keys.extend(vars.iter().map(|(k, _)| k.as_str()).filter(|k| !RECORDED.contains(k)));
+
+    let command = format!(
+        r#". "$RIDHOME/env" >/dev/null 2>&1; . "{}"; for k in {}; do printf '%s=%s\n' "$k" "${{!k}}"; done"#,
+        path.display(),
+        keys.join(" ")
+    );
+
+    match static_exec(&command) {
+        Ok(out) => out
+            .lines()
+            .filter_map(|l| l.split_once('='))
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        Err(e) => {
+            vpr!("Failed to read the effective build environment: {}", e);
+            BTreeMap::new()
+        }
+    }
+}
@@ -2,13 +2,17 @@
 //
 // defines functions related to command execution
 
-use crate::paths::TMPRID;
+use crate::signals::{Defer, Group};
 use crate::{erm, pr};
+use indicatif::ProgressBar;
 use std::fs::OpenOptions as OO;
 use std::io::{self, BufRead, Write};
-use std::process::{Command, Stdio};
+use std::path::Path;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
 pub fn static_exec(command: &str) -> io::Result<String> {
     let output = Command::new("bash").arg("-c").arg(command).output()?;
@@ -16,8 +20,7 @@ pub fn static_exec(command: &str) -> io::Result<String> {
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
+        Err(io::Error::other(
             format!(
                 "Command failed: {}",
                 String::from_utf8_lossy(&output.stderr)
@@ -26,32 +29,54 @@ pub fn static_exec(command: &str) -> io::Result<String> {
     }
 }
 
-pub fn exec(command: &str) -> io::Result<()> {
+fn wait(child: &Child) -> io::Result<(ExitStatus, Duration)> {
+    // wait4 reports the cpu time of the child and everything it waited on
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let pid = child.id() as libc::pid_t;
+
+    if unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } < 0 {
+        return Err(io::Error::last_os_error())
+    }
+
+    let tv = |t: libc::timeval| Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64);
+    Ok((ExitStatus::from_raw(status), tv(usage.ru_utime) + tv(usage.ru_stime)))
+}
+
+pub fn exec(command: &str, log: &Path, bar: Option<&ProgressBar>) -> io::Result<(ExitStatus, Duration)> {
+    // with a bar, output goes only to the log and the bar shows the latest line
+    // the child leads its own process group, to which rid forwards signals;
+    // exiting is deferred until the group is registered, so the child isn't orphaned
+    let defer = Defer::new();
     let mut child = Command::new("bash")
         .arg("-c")
         .arg(command)
+        .process_group(0)
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
+    let _group = Group::register(child.id());
+    drop(defer);
 
     let stdout = child.stdout.take().unwrap();
     let stderr = child.stderr.take().unwrap();
 
-    let log_file = Arc::new(Mutex::new(
-        OO::new()
-            .append(true)
-            .create(true)
-            .open(format!("{}/rid.log", TMPRID.display()))
-            .expect("Failed to open log file"),
-    ));
+    let mut log_file = OO::new().append(true).create(true).open(log)?;
+    writeln!(log_file, "$ {}", command)?;
+    let log_file = Arc::new(Mutex::new(log_file));
 
     let log_file_stdout = Arc::clone(&log_file);
+    let bar_stdout = bar.cloned();
     let stdout_thread = thread::spawn(move || {
         let reader = io::BufReader::new(stdout);
         for line in reader.lines() {
             match line {
                 Ok(line) => {
-                    pr!("{}", line);
+                    match &bar_stdout {
+                        Some(b) => b.set_message(line.clone()),
+                        None => pr!("{}", line),
+                    }
                     let log_line = format!("{}\n", line);
                     let mut log_file = log_file_stdout.lock().unwrap();
                     let _ = write!(log_file, "{}", log_line);
@@ -62,12 +87,16 @@ pub fn exec(command: &str) -> io::Result<()> {
     });
 
     let log_file_stderr = Arc::clone(&log_file);
+    let bar_stderr = bar.cloned();
     let stderr_thread = thread::spawn(move || {
         let reader = io::BufReader::new(stderr);
         for line in reader.lines() {
             match line {
                 Ok(line) => {
-                    pr!("\x1b[{}{}", CONFIG.colors.stderr, line);
+                    match &bar_stderr {
+                        Some(b) => b.set_message(line.clone()),
+                        None => pr!("\x1b[{}{}", CONFIG.colors.stderr, line),
+                    }
                     let log_line = format!("[ERR] {}\n", line);
                     let mut log_file = log_file_stderr.lock().unwrap();
                     let _ = write!(log_file, "{}", log_line);
@@ -77,9 +106,9 @@ pub fn exec(command: &str) -> io::Result<()> {
         }
     });
 
-    let _ = child.wait()?;
+    let result = wait(&child)?;
     stdout_thread.join().unwrap();
     stderr_thread.join().unwrap();
 
-    Ok(())
+    Ok(result)
 }
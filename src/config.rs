@@ -3,6 +3,7 @@
 
 use lazy_static::lazy_static;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
@@ -10,11 +11,68 @@ use std::sync::Arc;
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub behavior: BehaviorConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
     pub colors: ColorsConfig,
+    #[serde(default)]
+    pub env: EnvConfig,
     pub linkval: LinkvalConfig,
+    #[serde(default)]
+    pub logs: LogsConfig,
+    #[serde(default)]
+    pub test: TestConfig,
+    #[serde(default)]
+    pub upgrade: UpgradeConfig,
     pub upstream: UpstreamConfig,
 }
 
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct TestConfig {
+    pub all: bool,
+    pub packages: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct UpgradeConfig {
+    pub hold: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct BuildConfig {
+    pub jobs: usize,
+    pub reference: String,
+    pub sandbox: bool,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        BuildConfig { jobs: 1, reference: "binutils".to_string(), sandbox: false }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct EnvConfig {
+    pub global: BTreeMap<String, String>,
+    pub repo: BTreeMap<String, BTreeMap<String, String>>,
+    pub set: BTreeMap<String, BTreeMap<String, String>>,
+    pub package: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogsConfig {
+    pub keep: usize,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        LogsConfig { keep: 5 }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct LinkvalConfig {
     pub retry_count: u8,
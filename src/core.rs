@@ -2,24 +2,248 @@
 //
 // defines core functionality
 
+use crate::buildenv;
 use crate::cmd::{static_exec, exec};
 use crate::config::CONFIG;
-use crate::package::Package;
-use crate::paths::{BUILDING, BIN, SOURCES, REPO};
-use crate::resolve::find_dependants;
+use crate::events;
+use crate::flags::{PRETEND, SANDBOX, TEST};
+use crate::log;
+use crate::package::{Package, TestOutcome};
+use crate::paths::{BUILDING, BIN, EXTRACTION, JOBS, SOURCES, REPO};
+use crate::resolve::installed_dependants;
+use crate::signals::{self, Defer};
+use crate::stage;
+use crate::triggers;
+use crate::timing::Timing;
 use crate::utils::{display_list, mkdir};
-use crate::{erm, yn, vpr, die};
+use crate::{erm, yn, vpr, die, pr};
 use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::json;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::fmt;
 use std::fs::{self, File, read_dir, DirEntry};
 use std::io::{self, Write};
-use std::path::Path;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
 use ureq::{Response, get};
 
-pub fn mint(a: char, p: &Package) {
-    let command = format!(r#"RIDREPO="{}" {}/mint {} {}"#, &*REPO, BIN.display(), a, p.name);
-    if let Err(e) = exec(&command) {
-        die!("Failed to evaluate action '{}': {}", a, e)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Fetch,
+    Extract,
+    Install,
+    Update,
+    Remove,
+    Inspect,
+    Merge,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Phase::Fetch   => "fetch",
+            Phase::Extract => "extract",
+            Phase::Install => "install",
+            Phase::Update  => "update",
+            Phase::Remove  => "remove",
+            Phase::Inspect => "inspect",
+            Phase::Merge   => "merge",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildError {
+    pub phase: Phase,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub message: String,
+}
+
+impl BuildError {
+    fn from_status(phase: Phase, status: ExitStatus) -> Self {
+        BuildError { phase, code: status.code(), signal: status.signal(), message: String::new() }
+    }
+
+    fn from_message(phase: Phase, message: impl ToString) -> Self {
+        BuildError { phase, code: None, signal: None, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(c), _) => write!(f, "{} phase failed with exit code {}", self.phase, c),
+            (_, Some(s)) => write!(f, "{} phase was killed by signal {}", self.phase, s),
+            _ => write!(f, "{} phase failed: {}", self.phase, self.message),
+        }
+    }
+}
+
+pub struct Job {
+    pub name: String,
+    pub log: PathBuf,
+    pub root: PathBuf,
+    pub building: PathBuf,
+    pub extraction: PathBuf,
+    pub stage: Option<PathBuf>,
+    pub bar: Option<ProgressBar>,
+    pub env: BTreeMap<String, String>,
+    pub tests: bool,
+}
+
+fn sandboxed() -> bool {
+    *SANDBOX.lock().unwrap() || CONFIG.build.sandbox
+}
+
+impl Job {
+    fn new(p: &Package, log: PathBuf, building: PathBuf, extraction: PathBuf, bar: Option<ProgressBar>, builds: bool) -> Self {
+        // builds install into $RIDSTAGE, which is merged into / afterwards, unless
+        // the metafile sets STAG="no"
+        let root = JOBS.join(p.to_string());
+        let stage = (!p.live).then(|| root.join("stage"));
+        if let Some(stage) = &stage { mkdir(stage) }
+
+        // layered overrides are sourced by mint after $RIDHOME/env; removals get none
+        mkdir(&root);
+        let vars = if builds { buildenv::overrides(p) } else { Vec::new() };
+        buildenv::write(&root.join("env"), &vars);
+        let env = if builds { buildenv::effective(&root.join("env"), &vars) } else { BTreeMap::new() };
+        env.iter().for_each(|(k, v)| log::append(&log, &format!("# env: {}={}", k, v)));
+
+        // test suites write their result to $RIDCHECK, which is writable when sandboxed;
+        // removals never run check, so they have no test result
+        let tests = builds && (*TEST.lock().unwrap() || CONFIG.test.all || CONFIG.test.packages.contains(&p.name));
+        if tests { mkdir(&root.join("tmp")) }
+
+        Job { name: p.name.clone(), log, root, building, extraction, stage, bar, env, tests }
+    }
+
+    pub fn shared(p: &Package, log: PathBuf, builds: bool) -> Self {
+        Job::new(p, log, BUILDING.clone(), EXTRACTION.clone(), None, builds)
+    }
+
+    pub fn isolated(p: &Package, log: PathBuf, bar: ProgressBar) -> Self {
+        // parallel builds each get their own $RIDBUILDING and $RIDEXTRACTION
+        let root = JOBS.join(p.to_string());
+        let (building, extraction) = (root.join("building"), root.join("extraction"));
+        mkdir(&building);
+        mkdir(&extraction);
+        Job::new(p, log, building, extraction, Some(bar), true)
+    }
+
+    fn env(&self, staged: bool) -> String {
+        let mut env = format!(
+            r#"RIDBUILDING="{}" RIDEXTRACTION="{}" RIDDEST="{}" RIDENV="{}""#,
+            self.building.display(), self.extraction.display(), self.root.join("tmp/dest").display(), self.root.join("env").display()
+        );
+        if self.tests {
+            env += &format!(r#" RIDTEST="1" RIDCHECK="{}""#, self.root.join("tmp/check").display());
+        }
+        if let Some(stage) = self.stage.as_ref().filter(|_| staged) {
+            env += &format!(r#" RIDSTAGE="{0}" DESTDIR="{0}""#, stage.display());
+            if sandboxed() { env += &format!(r#" TMPDIR="{}""#, self.root.join("tmp").display()) }
+        }
+        env
+    }
+
+    fn exec(&self, command: &str, staged: bool) -> io::Result<(ExitStatus, Duration)> {
+        exec(&format!("{} {}", self.env(staged), command), &self.log, self.bar.as_ref())
+    }
+
+    pub fn hook(&self, name: &str, command: &str) -> io::Result<ExitStatus> {
+        // hooks act on the live system, so they're never sandboxed
+        log::append(&self.log, &format!("# hook: {}", name));
+        exec(command, &self.log, self.bar.as_ref()).map(|(status, _)| status)
+    }
+
+    fn sandbox(&self, command: &str) -> String {
+        // only the job's directories are writable inside the sandbox, $RIDDEST being under tmp
+        if !sandboxed() { return command.to_string() }
+        let Some(stage) = &self.stage else {
+            erm!("Not sandboxing '{}' since it installs straight to /", self.name);
+            return command.to_string()
+        };
+        let writable = [&self.building, &self.extraction, stage, &self.root.join("tmp")]
+            .iter()
+            .map(|d| format!(r#""{}""#, d.display()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{}/sb {} -- {}", BIN.display(), writable, command)
+    }
+
+    pub fn merge(&self) -> Result<(), BuildError> {
+        // the staged tree is checked before anything reaches the live system
+        let Some(stage) = &self.stage else {
+            triggers::untracked();
+            return Ok(())
+        };
+        let files = stage::inspect(&self.name, stage).map_err(|e| BuildError::from_message(Phase::Inspect, e))?;
+        let mut merged = Vec::new();
+        let result = stage::merge(stage, Path::new("/"), &mut merged);
+        vpr!("Merged {} files from '{}'", merged.len(), stage.display());
+        if let Err(e) = result {
+            // what did reach the live system still belongs to this package
+            stage::claim(&self.name, &merged);
+            return Err(BuildError::from_message(Phase::Merge, e))
+        }
+        if files.is_empty() { triggers::untracked() } else { stage::record(&self.name, &merged) }
+        Ok(())
+    }
+
+    pub fn tests(&self) -> Option<TestOutcome> {
+        // a build that never reached its check phase counts as skipped
+        if !self.tests { return None }
+        match fs::read_to_string(self.root.join("tmp/check")).unwrap_or_default().trim() {
+            "passed" => Some(TestOutcome::Passed),
+            "failed" => Some(TestOutcome::Failed),
+            _ => Some(TestOutcome::Skipped),
+        }
+    }
+
+    pub fn scrap(&self, p: &Package) {
+        let tree = self.building.join(p.to_string());
+        if !tree.exists() { return }
+        if let Err(e) = fs::remove_dir_all(&tree) {
+            erm!("Failed to remove build directory '{}': {}", tree.display(), e)
+        }
+    }
+
+    pub fn clean(&self) {
+        if !self.root.exists() { return }
+        if let Err(e) = fs::remove_dir_all(&self.root) {
+            erm!("Failed to remove job directory '{}': {}", self.root.display(), e)
+        }
+    }
+}
+
+pub fn mint(a: char, p: &Package, job: &Job) -> Result<Timing, BuildError> {
+    let phase = match a {
+        'i' => Phase::Install,
+        'u' => Phase::Update,
+        'r' => Phase::Remove,
+        _ => die!("Invalid mint action '{}'", a),
+    };
+
+    // removals act on the live system, so they're never sandboxed
+    let live = phase == Phase::Remove;
+    let mut command = format!("{}/mint {} {}", BIN.display(), a, p.name);
+    if !live { command = job.sandbox(&command) }
+
+    let command = format!(r#"RIDREPO="{}" {}"#, &*REPO, command);
+    let start = Instant::now();
+    match job.exec(&command, !live) {
+        Ok((status, cpu)) if status.success() => {
+            // recorded files are removed after rdir has had a chance to run
+            if live { stage::forget(&p.name) }
+            Ok(Timing { wall: start.elapsed(), cpu })
+        }
+        Ok((status, _)) => Err(BuildError::from_status(phase, status)),
+        Err(e) => Err(BuildError::from_message(phase, e)),
     }
 }
 
@@ -60,8 +284,13 @@ fn dl_bar(
             let mut reader = r.into_reader();
             let mut buffer = [0; 8192]; 
             let mut downloaded = 0;
+            let mut reported = 0;
 
             loop {
+                if signals::interrupted().is_some() {
+                    return Err("Interrupted".into())
+                }
+
                 let bytes_read = reader.read(&mut buffer)?;
                 if bytes_read == 0 { break }
 
@@ -71,6 +300,12 @@ fn dl_bar(
                 bar.set_position(downloaded);
                 // dbg!(&downloaded);
 
+                // progress events are emitted every percent or so
+                if downloaded - reported >= (length / 100).max(1 << 16) {
+                    events::emit("download_progress", json!({ "file": file_name, "bytes": downloaded, "total": length }));
+                    reported = downloaded;
+                }
+
                 if length < downloaded {
                     bar.set_length(downloaded);
                 }
@@ -85,66 +320,88 @@ fn dl_bar(
     Ok(bar)
 }
 
-pub fn download(p: Package, force: bool) {
+pub fn dl(url: &str, file_name: &str, file_path: &Path) -> Result<(), Box<dyn Error>> {
+    // downloads go to a .part file so an interrupted download never looks complete
+    let _defer = Defer::new();
+    let part = PathBuf::from(format!("{}.part", file_path.display()));
+
+    vpr!("Downloading '{}' from '{}'...", file_name, url);
+    events::emit("download_start", json!({ "file": file_name, "url": url }));
+    let result: Result<(), Box<dyn Error>> = get(url)
+        .set("Accept-Encoding", "none")
+        .call()
+        .map_err(|e| e.into())
+        .and_then(|r| dl_bar(r, file_name, &part))
+        .and_then(|_| fs::rename(&part, file_path).map_err(|e| e.into()));
+
+    if let Err(e) = result {
+        if part.exists() && let Err(e) = fs::remove_file(&part) {
+            erm!("Failed to remove partial download '{}': {}", part.display(), e)
+        }
+        events::emit("download_end", json!({ "file": file_name, "ok": false, "error": e.to_string() }));
+        return Err(format!("Failed to download url '{}': {}", url, e).into())
+    }
+
+    events::emit("download_end", json!({ "file": file_name, "ok": true }));
+    Ok(())
+}
+
+pub fn download(p: Package, force: bool) -> Result<(), Box<dyn Error>> {
     let tarball_link = &p.link;
     let tarball = format!("{}.tar", p);
     let tarball_path = &SOURCES.join(&tarball);
     let extra_links = p.downloads;
 
     for url in extra_links {
-        let file_name = url.split('/').next_back().expect("Invalid url");
+        let file_name = url.split('/').next_back().ok_or("Invalid url")?;
         let file_path = &SOURCES.join(file_name);
 
         if !file_path.exists() || force {
-            vpr!("Downloading '{}' from '{}'...", file_name, url);
-            let r = get(&url).set("Accept-Encoding", "none").call().expect("Failed to get url");
-
-            if let Err(e) = dl_bar(r, file_name, file_path) {
-                die!("Failed to download url '{}': {}", url, e)
-            }
+            dl(&url, file_name, file_path)?;
         }
     }
 
     if !tarball_link.is_empty() && (!tarball_path.exists() || force) {
-        vpr!("Downloading '{}' from '{}'...", tarball, tarball_link);
-        let r = get(tarball_link).set("Accept-Encoding", "none").call().expect("Failed to get tarball");
-
-        if let Err(e) = dl_bar(r, &tarball, tarball_path) {
-            die!("Failed to download url '{}': {}", tarball_link, e)
-        }
+        dl(tarball_link, &tarball, tarball_path)?;
     }
+
+    Ok(())
 }
 
 // NOTE: whether a package should be extracted is now handled under pm.install()
-pub fn extract(p: &Package) -> Result<(), Box<dyn Error>> {
+pub fn extract(p: &Package, job: &Job) -> Result<(), BuildError> {
     if p.link.is_empty() {
-        let path = &BUILDING.join(p.to_string());
+        let path = &job.building.join(p.to_string());
         mkdir(path);
         return Ok(())
     }
 
     let command = format!("{}/xt {}", BIN.display(), p);
-    if exec(&command).is_err() {
-        vpr!("Corrupt tarball detected!");
-        return Err("corrupt tarball".into())
-    }
-    
-    Ok(())
+    events::emit("extract_start", json!({ "package": p.name, "version": p.version }));
+    let result = match job.exec(&command, false) {
+        Ok((status, _)) if status.success() => Ok(()),
+        Ok((status, _)) => {
+            vpr!("Corrupt tarball detected!");
+            Err(BuildError::from_status(Phase::Extract, status))
+        }
+        Err(e) => Err(BuildError::from_message(Phase::Extract, e)),
+    };
+    events::emit("extract_end", json!({ "package": p.name, "version": p.version, "ok": result.is_ok() }));
+    result
 }
 
-pub fn fetch(p: &Package) {
-    download(p.clone(), false);
-    if extract(p).is_err() {
-        download(p.clone(), true);
-        if extract(p).is_err() {
-            die!("Failed to recover from corrupt tarball")
-        }
+pub fn fetch(p: &Package, job: &Job) -> Result<(), BuildError> {
+    download(p.clone(), false).map_err(|e| BuildError::from_message(Phase::Fetch, e))?;
+    if extract(p, job).is_err() {
+        download(p.clone(), true).map_err(|e| BuildError::from_message(Phase::Fetch, e))?;
+        extract(p, job)?;
     }
+    Ok(())
 }
 
 pub fn confirm_removal(pkg: &Package, pkglist: &[Package]) -> bool {
     vpr!("Checking dependants for '{}'", pkg);
-    let dependants = find_dependants(&pkg.clone(), pkglist.to_vec());
+    let dependants = installed_dependants(std::slice::from_ref(pkg), pkglist);
     let len = dependants.len();
 
     vpr!("Found {} dependants", len);
@@ -152,6 +409,7 @@ pub fn confirm_removal(pkg: &Package, pkglist: &[Package]) -> bool {
 
     erm!("Found {} dependant packages:", len);
     display_list(&dependants);
+    vpr!("Tip: Use --why {} to see through which chains they depend on it", pkg.name);
 
     let message = format!("Remove '{}' ({} dependants)?", pkg, len);
     yn!(&message, false)
@@ -185,7 +443,10 @@ pub fn prune_sources(p: &Package) -> u8 {
             .filter_map(Result::ok)
             .filter(|entry| is_removable(entry, p))
             .for_each(|entry| {
-                if remove_file(&entry).is_ok() {
+                if *PRETEND.lock().unwrap() {
+                    pr!("Would remove {:?}", entry.file_name());
+                    num_removed += 1;
+                } else if remove_file(&entry).is_ok() {
                     num_removed += 1;
                     vpr!("Removed {:?}", entry);
                 }
@@ -0,0 +1,49 @@
+// src/events.rs
+//
+// responsible for the json-lines event stream written by --events
+
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::fs::{File, OpenOptions as OO};
+use std::io::{self, Write};
+use std::os::fd::FromRawFd;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    static ref SINK: Mutex<Option<File>> = Mutex::new(None);
+}
+
+pub fn open(target: &str) {
+    // a number is taken as an already open file descriptor, anything else as a path
+    let file = match target.parse::<i32>() {
+        // the descriptor has to be open before a File may own it
+        Ok(fd) if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 => Err(io::Error::last_os_error()),
+        Ok(fd) => Ok(unsafe { File::from_raw_fd(fd) }),
+        Err(_) => OO::new().append(true).create(true).open(target),
+    };
+
+    match file {
+        Ok(f) => *SINK.lock().unwrap() = Some(f),
+        Err(e) => eprintln!("Failed to open event stream '{}': {}", target, e),
+    }
+}
+
+pub fn emit(event: &str, fields: Value) {
+    // events never go through the output macros, since erm! itself emits events
+    let mut sink = SINK.lock().unwrap();
+    let Some(f) = sink.as_mut() else { return };
+
+    // event and time lead each line, followed by the event's own fields
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    let fields = match fields {
+        Value::Object(m) if !m.is_empty() => format!(",{}", &Value::Object(m).to_string()[1..]),
+        _ => "}".to_string(),
+    };
+    let line = format!(r#"{{"event":{},"time":{}{}"#, Value::from(event), time, fields);
+
+    if let Err(e) = writeln!(f, "{}", line) {
+        eprintln!("Failed to write to event stream, disabling it: {}", e);
+        *sink = None;
+    }
+}
@@ -2,17 +2,42 @@
 //
 // stores flags for global use
 
+use crate::args::Args;
 use lazy_static::lazy_static;
 use std::sync::Mutex;
 
 lazy_static! {
+    pub static ref ALL:     Mutex<bool> = Mutex::new(false);
     pub static ref FORCE:   Mutex<bool> = Mutex::new(false);
+    pub static ref KEEP_GOING: Mutex<bool> = Mutex::new(false);
+    pub static ref PRETEND: Mutex<bool> = Mutex::new(false);
     pub static ref QUIET:   Mutex<bool> = Mutex::new(false);
+    pub static ref REBUILD_DEPENDANTS: Mutex<bool> = Mutex::new(false);
+    pub static ref SANDBOX: Mutex<bool> = Mutex::new(false);
+    pub static ref TEST:    Mutex<bool> = Mutex::new(false);
+    pub static ref TREE:    Mutex<bool> = Mutex::new(false);
+    pub static ref UPDATE_DEPS: Mutex<bool> = Mutex::new(false);
     pub static ref VERBOSE: Mutex<bool> = Mutex::new(false);
+
+    pub static ref DEPTH:   Mutex<Option<usize>> = Mutex::new(None);
+    pub static ref MAKE_JOBS: Mutex<Option<usize>> = Mutex::new(None);
+    pub static ref PARALLEL: Mutex<Option<usize>> = Mutex::new(None);
 }
 
-pub fn set_flags(force: bool, quiet: bool, verbose: bool) {
-    *FORCE.lock().unwrap()   = force;
-    *QUIET.lock().unwrap()   = quiet;
-    *VERBOSE.lock().unwrap() = verbose;
+pub fn set_flags(args: &Args) {
+    *ALL.lock().unwrap()     = args.all;
+    *FORCE.lock().unwrap()   = args.force;
+    *KEEP_GOING.lock().unwrap() = args.keep_going;
+    *PRETEND.lock().unwrap() = args.pretend;
+    *QUIET.lock().unwrap()   = args.quiet;
+    *REBUILD_DEPENDANTS.lock().unwrap() = args.rebuild_dependants;
+    *SANDBOX.lock().unwrap() = args.sandbox;
+    *TEST.lock().unwrap()    = args.test;
+    *TREE.lock().unwrap()    = args.tree;
+    *UPDATE_DEPS.lock().unwrap() = args.update_deps;
+    *VERBOSE.lock().unwrap() = args.verbose;
+
+    *DEPTH.lock().unwrap()   = args.depth;
+    *MAKE_JOBS.lock().unwrap() = args.make_jobs;
+    *PARALLEL.lock().unwrap() = args.parallel;
 }
@@ -0,0 +1,159 @@
+// src/graph.rs
+//
+// responsible for dependency tree views and graph export
+
+use crate::flags::DEPTH;
+use crate::package::Package;
+use crate::resolve::reverse_index;
+use crate::{die, vpr};
+use serde_json::{json, to_string_pretty};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Dependencies,
+    Dependants,
+}
+
+fn label(p: &Package) -> String {
+    let marker = if p.installed_version.is_empty() {
+        ""
+    } else if p.installed_version != p.version {
+        " \x1b[31;1m[outdated]"
+    } else {
+        " \x1b[36;1m[installed]"
+    };
+
+    format!("{}{}\x1b[0m", p, marker)
+}
+
+struct Tree<'a> {
+    pkglist: &'a [Package],
+    reverse: HashMap<String, Vec<String>>,
+    direction: Direction,
+    max_depth: Option<usize>,
+    shown: HashSet<String>,
+}
+
+impl Tree<'_> {
+    fn children(&self, pkg: &Package) -> Vec<Package> {
+        let names = match self.direction {
+            Direction::Dependencies => pkg.deps.clone(),
+            Direction::Dependants => self.reverse.get(&pkg.name).cloned().unwrap_or_default(),
+        };
+
+        names.iter().map(|n| Package::new(n, self.pkglist.to_vec())).collect()
+    }
+
+    fn walk(&mut self, pkg: &Package, prefix: &str, depth: usize) {
+        let kids = self.children(pkg);
+        let len = kids.len();
+
+        for (i, kid) in kids.iter().enumerate() {
+            let last = i + 1 == len;
+            let branch = if last { "└── " } else { "├── " };
+            let has_kids = !self.children(kid).is_empty();
+
+            if !self.shown.insert(kid.name.clone()) && has_kids {
+                println!("{}{}{} (see above)", prefix, branch, label(kid));
+                continue
+            }
+
+            if self.max_depth.is_some_and(|m| depth + 1 >= m) && has_kids {
+                println!("{}{}{} ...", prefix, branch, label(kid));
+                continue
+            }
+
+            println!("{}{}{}", prefix, branch, label(kid));
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.walk(kid, &prefix, depth + 1);
+        }
+    }
+}
+
+pub fn tree(pkg: &Package, pkglist: &[Package], direction: Direction) {
+    let mut tree = Tree {
+        pkglist,
+        reverse: match direction {
+            Direction::Dependants => reverse_index(pkglist),
+            Direction::Dependencies => HashMap::new(),
+        },
+        direction,
+        max_depth: *DEPTH.lock().unwrap(),
+        shown: HashSet::from([pkg.name.clone()]),
+    };
+
+    println!("{}", label(pkg));
+    tree.walk(pkg, "", 0);
+}
+
+fn closure(pkgs: &[Package], pkglist: &[Package]) -> Vec<Package> {
+    // everything reachable from pkgs, without requiring a valid build order
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    let mut stack: Vec<Package> = pkgs.to_vec();
+
+    while let Some(p) = stack.pop() {
+        if !seen.insert(p.name.clone()) { continue }
+        stack.extend(p.deps.iter().map(|d| Package::new(d, pkglist.to_vec())));
+        nodes.push(p);
+    }
+
+    nodes.sort();
+    nodes
+}
+
+pub fn export(pkgs: &[Package], pkglist: &[Package], format: &str) {
+    let nodes = if pkgs.is_empty() { pkglist.to_vec() } else { closure(pkgs, pkglist) };
+    let names: HashSet<&str> = nodes.iter().map(|p| p.name.as_str()).collect();
+
+    let edges: Vec<(&str, &str)> = nodes
+        .iter()
+        .flat_map(|p| p.deps.iter().map(move |d| (p.name.as_str(), d.as_str())))
+        .filter(|(_, d)| names.contains(d))
+        .collect();
+
+    vpr!("Exporting {} nodes and {} edges as {}", nodes.len(), edges.len(), format);
+
+    match format {
+        "dot" => {
+            println!("digraph rid {{");
+            println!("  rankdir=LR;");
+            println!("  node [shape=box];");
+            for p in nodes.iter() {
+                let fill = match p.installed_version.as_str() {
+                    "" => "white",
+                    v if v != p.version => "salmon",
+                    _ => "lightblue",
+                };
+                println!("  \"{}\" [label=\"{}\", style=filled, fillcolor={}];", p.name, p, fill);
+            }
+            for (from, to) in edges.iter() {
+                println!("  \"{}\" -> \"{}\";", from, to);
+            }
+            println!("}}");
+        }
+        "json" => {
+            let nodes: Vec<_> = nodes
+                .iter()
+                .map(|p| json!({
+                    "name": p.name,
+                    "version": p.version,
+                    "installed_version": p.installed_version,
+                    "status": format!("{:?}", p.status),
+                    "dependants": edges.iter().filter(|(_, to)| *to == p.name).count(),
+                }))
+                .collect();
+            let edges: Vec<_> = edges
+                .iter()
+                .map(|(from, to)| json!({ "from": from, "to": to }))
+                .collect();
+
+            match to_string_pretty(&json!({ "nodes": nodes, "edges": edges })) {
+                Ok(s) => println!("{}", s),
+                Err(e) => die!("Failed to serialize graph: {}", e),
+            }
+        }
+        _ => die!("Unsupported graph format '{}'", format),
+    }
+}
@@ -0,0 +1,135 @@
+// src/hooks.rs
+//
+// responsible for running hooks before and after package actions
+
+use crate::core::Job;
+use crate::package::Package;
+use crate::paths::HOOKS;
+use crate::plan::{Action, Step};
+use crate::sets::{expand_set, is_set};
+use crate::utils::glob;
+use crate::{erm, vpr};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum When {
+    Pre,
+    #[default]
+    Post,
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            When::Pre  => write!(f, "pre"),
+            When::Post => write!(f, "post"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Hook {
+    #[serde(skip)]
+    pub name: String,
+    pub when: When,
+    pub on: Vec<String>,
+    pub packages: Vec<String>,
+    pub run: String,
+    pub update: Vec<String>,
+    pub rebuild: Vec<String>,
+}
+
+fn load() -> Vec<Hook> {
+    // $RIDHOME/hooks/<name>.toml
+    let Ok(entries) = fs::read_dir(&*HOOKS) else { return Vec::new() };
+    let mut paths: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let hook = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| toml::from_str::<Hook>(&s).map_err(|e| e.to_string()));
+
+            match hook {
+                Ok(h) => {
+                    vpr!("Loaded hook '{}'", name);
+                    Some(Hook { name, ..h })
+                }
+                Err(e) => {
+                    erm!("Ignoring hook '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+lazy_static! {
+    pub static ref HOOK_LIST: Vec<Hook> = load();
+}
+
+fn event(action: &Action) -> Option<&'static str> {
+    match action {
+        Action::Install | Action::Rebuild => Some("install"),
+        Action::Update => Some("update"),
+        Action::Remove => Some("remove"),
+        Action::Hold | Action::Skip => None,
+    }
+}
+
+impl Hook {
+    fn matches(&self, when: When, step: &Step, pkglist: &[Package]) -> bool {
+        let Some(event) = event(&step.action) else { return false };
+        let name = &step.pkg.name;
+
+        self.when == when
+            && (self.on.is_empty() || self.on.iter().any(|o| o == event))
+            && self.packages.iter().any(|p| {
+                if is_set(p) {
+                    expand_set(p, &pkglist.to_vec()).contains(name)
+                } else {
+                    glob(p, name)
+                }
+            })
+    }
+
+    fn env(&self, when: When, step: &Step) -> String {
+        format!(
+            r#"export RIDHOOK="{}" RIDHOOKWHEN="{}" RIDHOOKACTION="{}" RIDHOOKPKG="{}" RIDHOOKVERS="{}";"#,
+            self.name, when, step.action, step.pkg.name, step.pkg.version
+        )
+    }
+}
+
+pub fn matching(when: When, step: &Step, pkglist: &[Package]) -> Vec<&'static Hook> {
+    HOOK_LIST.iter().filter(|h| h.matches(when, step, pkglist)).collect()
+}
+
+pub fn run(hook: &Hook, when: When, step: &Step, job: &Job) -> Vec<(String, Action)> {
+    // runs a hook's command into the job's log, returning the rid actions it queues
+    if !hook.run.is_empty() {
+        vpr!("Running {} hook '{}' for '{}'", when, hook.name, step.pkg);
+        let command = format!("{} {}", hook.env(when, step), hook.run);
+        match job.hook(&hook.name, &command) {
+            Ok(status) if status.success() => (),
+            Ok(status) => erm!("Hook '{}' failed for '{}': {}", hook.name, step.pkg, status),
+            Err(e) => erm!("Hook '{}' failed for '{}': {}", hook.name, step.pkg, e),
+        }
+    }
+
+    let update = hook.update.iter().map(|n| (n.clone(), Action::Update));
+    let rebuild = hook.rebuild.iter().map(|n| (n.clone(), Action::Rebuild));
+    update.chain(rebuild).collect()
+}
@@ -0,0 +1,127 @@
+// src/log.rs
+//
+// responsible for per-build log files
+
+use crate::config::CONFIG;
+use crate::core::BuildError;
+use crate::paths::LOGS;
+use crate::signals::interrupted;
+use crate::timing::{human, Timing};
+use crate::utils::mkdir;
+use crate::{die, erm, msg, pr, vpr};
+use std::fs::{self, OpenOptions as OO};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+pub fn append(log: &Path, line: &str) {
+    let result = OO::new()
+        .append(true)
+        .create(true)
+        .open(log)
+        .and_then(|mut f| writeln!(f, "{}", line));
+
+    if let Err(e) = result {
+        erm!("Failed to write to log '{}': {}", log.display(), e)
+    }
+}
+
+pub fn create(name: &str, stem: &str, header: &str) -> PathBuf {
+    // logs live under $RIDHOME/logs/<name>/<stem>-<unix>.log, with a counter
+    // (<stem>-<unix>.<n>.log) if another log was created in the same second
+    let dir = LOGS.join(name);
+    mkdir(&dir);
+
+    let time = now();
+    let log = (0..)
+        .map(|n| match n {
+            0 => dir.join(format!("{}-{}.log", stem, time)),
+            n => dir.join(format!("{}-{}.{}.log", stem, time, n)),
+        })
+        .find(|log| match OO::new().write(true).create_new(true).open(log) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => false,
+            Err(e) => {
+                erm!("Failed to create log '{}': {}", log.display(), e);
+                true
+            }
+            Ok(_) => true,
+        })
+        .unwrap();
+    append(&log, &format!("# rid: {}", header));
+    vpr!("Logging to '{}'", log.display());
+    log
+}
+
+pub fn finish(log: &Path, result: &Result<Timing, BuildError>) {
+    match result {
+        Ok(t) => append(log, &format!("# exit status: succeeded (wall {}, cpu {})", human(t.wall), human(t.cpu))),
+        Err(_) if let Some(sig) = interrupted() => append(log, &format!("# exit status: interrupted (signal {})", sig)),
+        Err(e) => append(log, &format!("# exit status: failed ({})", e)),
+    }
+}
+
+fn logs(name: &str) -> Vec<PathBuf> {
+    // oldest first
+    let mut logs: Vec<PathBuf> = match fs::read_dir(LOGS.join(name)) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "log"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    logs.sort_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok());
+    logs
+}
+
+fn failed(log: &Path) -> bool {
+    // hooks may log after the exit status, and interrupted builds count as failed
+    fs::read_to_string(log).is_ok_and(|s| {
+        s.lines()
+            .rfind(|l| l.starts_with("# exit status: "))
+            .is_some_and(|l| !l.starts_with("# exit status: succeeded"))
+    })
+}
+
+pub fn prune(name: &str) {
+    let logs = logs(name);
+    let keep = CONFIG.logs.keep;
+    if logs.len() <= keep { return }
+
+    for log in logs.iter().take(logs.len() - keep) {
+        vpr!("Removing old log '{}'", log.display());
+        if let Err(e) = fs::remove_file(log) {
+            erm!("Failed to remove old log '{}': {}", log.display(), e)
+        }
+    }
+}
+
+pub fn show(name: &str, only_failed: bool, last: bool) {
+    let logs = logs(name);
+    if logs.is_empty() { die!("No logs for '{}'", name) }
+
+    if !only_failed && !last {
+        msg!("Logs for '{}'", name);
+        for log in logs.iter() {
+            let status = if failed(log) { "\x1b[31;1mfailed" } else { "" };
+            pr!("  {} {}", log.display(), status);
+        }
+        return
+    }
+
+    let log = match logs.iter().rev().find(|l| !only_failed || failed(l)) {
+        Some(l) => l,
+        None => die!("No failed builds logged for '{}'", name),
+    };
+
+    msg!("Showing '{}'", log.display());
+    match fs::read_to_string(log) {
+        Ok(s) => print!("{}", s),
+        Err(e) => die!("Failed to read log '{}': {}", log.display(), e),
+    }
+}
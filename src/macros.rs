@@ -32,7 +32,9 @@ macro_rules! vpr {
 macro_rules! erm {
     ($($arg:tt)*) => {{
         use $crate::config::CONFIG;
-        eprintln!("\x1b[{}{}\x1b[0m", CONFIG.colors.danger, format!($($arg)*))
+        let m = format!($($arg)*);
+        $crate::events::emit("warning", serde_json::json!({ "message": m }));
+        eprintln!("\x1b[{}{}\x1b[0m", CONFIG.colors.danger, m)
     }};
 }
 
@@ -48,7 +50,9 @@ macro_rules! msg {
 macro_rules! die {
     ($($arg:tt)*) => {{
         use $crate::config::CONFIG;
-        panic!("\x1b[{}{}\x1b[0m", CONFIG.colors.danger, format!($($arg)*))
+        let m = format!($($arg)*);
+        $crate::events::emit("error", serde_json::json!({ "message": m }));
+        panic!("\x1b[{}{}\x1b[0m", CONFIG.colors.danger, m)
     }};
 }
 
@@ -8,28 +8,52 @@ use tracking::load_pkglist;
 use utils::pkg_search;
 
 mod args;
+mod buildenv;
 mod checks;
 mod cmd;
 mod config;
 mod core;
+mod events;
 mod examples;
 mod flags;
+mod graph;
+mod hooks;
 mod init;
 mod linkval;
+mod log;
 mod macros;
 mod package;
 mod paths;
+mod plan;
 mod pm;
 mod resolve;
+mod scaffold;
 mod sets;
+mod signals;
+mod stage;
+mod timing;
 mod tracking;
+mod transaction;
+mod triggers;
 mod upstream;
 mod utils;
 
 fn main() {
     let args = args::init_args();
     init::init();
-    flags::set_flags(args.force, args.quiet, args.verbose);
+    flags::set_flags(&args);
+    signals::install();
+    if let Some(target) = &args.events { events::open(target) }
+
+    if let Some(new) = &args.new {
+        scaffold::new(&new[0], &new[1]);
+        return
+    }
+
+    if let Some(pkg) = &args.log {
+        log::show(pkg, args.failed, args.last);
+        return
+    }
 
     vpr!("Set repo to {}", &*REPO);
     let mut pkglist = load_pkglist();
@@ -96,10 +120,22 @@ fn main() {
         install_with_dependencies,
         update,
         update_with_dependencies,
+        upgrade,
+        resume,
+        abort,
+        calibrate,
         news,
         prune,
         check_upstream,
         validate_links,
         sync,
     ]);
+
+    if let Some(format) = &args.graph {
+        pm.graph(format);
+    }
+
+    if let Some(why) = &args.why {
+        pm.why(why);
+    }
 }
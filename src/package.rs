@@ -3,11 +3,13 @@
 // defines core package-related functionality
 
 use crate::cmd::static_exec;
-use crate::paths::{BIN, REPO};
+use crate::paths::{BIN, META, REPO};
 use crate::sets::handle_sets;
+use crate::utils::hash_file;
 use crate::{die, vpr};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -17,15 +19,69 @@ pub enum PackageStatus {
     Removed,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum BuildOutcome {
+    #[default]
+    Succeeded,
+    Failed,
+    Interrupted,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestOutcome::Passed  => write!(f, "passed"),
+            TestOutcome::Failed  => write!(f, "failed"),
+            TestOutcome::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct BuildRecord {
+    pub version: String,
+    pub outcome: BuildOutcome,
+    pub phase: String,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub log: String,
+    pub wall: f64,
+    pub cpu: f64,
+    pub env: BTreeMap<String, String>,
+    pub tests: Option<TestOutcome>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Package {
+    #[serde(default)]
+    pub bootstrap: Vec<String>,
+    #[serde(default)]
+    pub build: Option<BuildRecord>,
     pub deps: Vec<String>,
     pub description: String,
     pub downloads: Vec<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    #[serde(default)]
+    pub hash: String,
     pub installed_version: String,
     pub link: String,
+    #[serde(default)]
+    pub live: bool,
     pub name: String,
     pub news: String,
+    #[serde(default)]
+    pub orphaned: bool,
+    #[serde(default)]
+    pub sbus: Option<f64>,
     pub status: PackageStatus,
     pub upstream: String,
     pub version: String,
@@ -74,12 +130,15 @@ impl Package {
     pub fn def(pkg_name: &str, pkglist: Vec<Package>) -> Package {
         vpr!("Forming {}", pkg_name);
 
+        let mut bootstrap = Vec::new();
         let mut deps = Vec::new();
         let mut description = String::new();
         let mut downloads = Vec::new();
         let mut link = String::new();
+        let mut live = false;
         let mut name = String::new();
         let mut news = String::new();
+        let mut sbus = None;
         let mut upstream = String::new();
         let mut version = String::new();
         let mut version_command = String::new();
@@ -97,9 +156,17 @@ impl Package {
                 _ if line.starts_with("LINK: ") => link = line[6..].trim().to_string(),
                 _ if line.starts_with("NAME: ") => name = line[6..].trim().to_string(),
                 _ if line.starts_with("NEWS: ") => news = line[6..].trim().to_string(),
+                _ if line.starts_with("SBUS: ") => sbus = line[6..].trim().parse().ok(),
+                _ if line.starts_with("STAG: ") => live = line[6..].trim() == "no",
                 _ if line.starts_with("UPST: ") => upstream = line[6..].trim().to_string(),
                 _ if line.starts_with("VCMD: ") => version_command = line[6..].trim().to_string(),
                 _ if line.starts_with("VERS: ") => version = line[6..].trim().to_string(),
+                _ if line.starts_with("BOOT: ") => {
+                    bootstrap = line[6..]
+                        .split_whitespace()
+                        .map(|s| s.to_string())
+                        .collect()
+                }
                 _ if line.starts_with("DEPS: ") => {
                     deps = line[6..]
                         .split_whitespace()
@@ -118,26 +185,31 @@ impl Package {
 
         if name.is_empty() { die!("Missing name for package: {}", pkg_name) }
 
+        let hash = hash_file(&META.join(&*REPO).join(pkg_name)).unwrap_or_default();
+
         if !pkglist.is_empty() {
             deps = handle_sets(deps, &pkglist);
         }
 
-        let (status, installed_version) = pkglist
-            .iter()
-            .find(|p| p.name == name)
-            .map_or((PackageStatus::Available, String::new()), |p| {
-                (p.status.clone(), p.installed_version.clone())
-            });
+        // tracking information is carried over from the existing entry
+        let prev = pkglist.iter().find(|p| p.name == name);
 
         Package {
+            bootstrap,
+            build: prev.and_then(|p| p.build.clone()),
             deps,
             description,
             downloads,
-            installed_version,
+            explicit: prev.is_some_and(|p| p.explicit),
+            hash,
+            installed_version: prev.map_or(String::new(), |p| p.installed_version.clone()),
             link,
+            live,
             name,
             news,
-            status,
+            orphaned: false,
+            sbus,
+            status: prev.map_or(PackageStatus::Available, |p| p.status.clone()),
             upstream,
             version,
             version_command,
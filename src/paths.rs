@@ -18,12 +18,19 @@ lazy_static! {
     pub static ref BIN:         PathBuf = RIDHOME.join("bin");
     pub static ref BUILDING:    PathBuf = wrap("RIDBUILDING");
     pub static ref DEST:        PathBuf = wrap("RIDDEST");
+    pub static ref ENVD:        PathBuf = RIDHOME.join("env.d");
     pub static ref EXTRACTION:  PathBuf = wrap("RIDEXTRACTION");
-    pub static ref FAILED:      PathBuf = wrap("RIDFAILED");
+    pub static ref FILES:       PathBuf = RIDHOME.join("files");
+    pub static ref HOOKS:       PathBuf = RIDHOME.join("hooks");
+    pub static ref JOBS:        PathBuf = TMPRID.join("jobs");
+    pub static ref LOGS:        PathBuf = RIDHOME.join("logs");
     pub static ref META:        PathBuf = wrap("RIDMETA");
     pub static ref PKGSJSON:    PathBuf = wrap("RIDPKGSJSON"); // unstable
+    pub static ref SBU:         PathBuf = RIDHOME.join("sbu");
     pub static ref SETS:        PathBuf = RIDHOME.join("sets");
     pub static ref SOURCES:     PathBuf = wrap("RIDSOURCES");
+    pub static ref TRIGGERS:    PathBuf = RIDHOME.join("triggers");
+    pub static ref TRANSACTION: PathBuf = RIDHOME.join("transaction.json");
     pub static ref TMPRID:      PathBuf = wrap("RIDTMP");
     pub static ref TRASH:       PathBuf = wrap("RIDTRASH");
 
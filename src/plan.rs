@@ -0,0 +1,385 @@
+// src/plan.rs
+//
+// responsible for planning transactions across several packages
+
+use crate::config::CONFIG;
+use crate::events;
+use crate::flags::{FORCE, UPDATE_DEPS};
+use crate::hooks::{self, When};
+use crate::package::{Package, PackageStatus};
+use crate::paths::SOURCES;
+use crate::resolve::{installed_dependants, is_bootstrap, resolve_all};
+use crate::timing::{annotate, human, total};
+use crate::utils::display_list;
+use crate::{erm, msg, pr, vpr};
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Action {
+    Install,
+    Update,
+    Rebuild,
+    Remove,
+    Hold,
+    Skip,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Action::Install => "install",
+            Action::Update  => "update",
+            Action::Rebuild => "rebuild",
+            Action::Remove  => "remove",
+            Action::Hold    => "hold",
+            Action::Skip    => "skip",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub pkg: Package,
+    pub action: Action,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            Action::Update | Action::Hold if !self.pkg.installed_version.is_empty() => write!(
+                f, "{:<8} {} {} -> {}",
+                self.action, self.pkg.name, self.pkg.installed_version, self.pkg.version
+            ),
+            _ => write!(f, "{:<8} {}", self.action, self.pkg),
+        }
+    }
+}
+
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub succeeded: Vec<Step>,
+    pub skipped: Vec<Step>,
+    pub failed: Vec<Step>,
+    pub remaining: Vec<Step>,
+    pub logs: HashMap<String, String>,
+}
+
+impl Summary {
+    pub fn display(&self) {
+        msg!(
+            "Summary: {} succeeded, {} skipped, {} failed, {} not attempted",
+            self.succeeded.len(),
+            self.skipped.len(),
+            self.failed.len(),
+            self.remaining.len(),
+        );
+
+        for (label, steps) in [
+            ("Succeeded", &self.succeeded),
+            ("Skipped", &self.skipped),
+            ("Failed", &self.failed),
+            ("Not attempted", &self.remaining),
+        ] {
+            if steps.is_empty() { continue }
+            pr!("{}:", label);
+            for step in steps.iter() {
+                match self.logs.get(&step.pkg.name) {
+                    Some(log) if label == "Failed" => pr!("  {} (log: {})", step, log),
+                    _ => pr!("  {}", step),
+                }
+            }
+        }
+    }
+}
+
+fn is_installed(pkg: &Package) -> bool {
+    matches!(pkg.status, PackageStatus::Installed)
+}
+
+pub fn is_current(pkg: &Package) -> bool {
+    pkg.installed_version == pkg.version && pkg.version != "9999"
+}
+
+fn repeated(steps: &[Step], pkg: &Package) -> Action {
+    // a package resolved twice follows its bootstrap dependency, and only needs
+    // rebuilding if it or that dependency is built in this plan
+    let built = |s: &&Step| matches!(s.action, Action::Install | Action::Update | Action::Rebuild);
+    if steps.iter().filter(built).any(|s| s.pkg.name == pkg.name || is_bootstrap(pkg, &s.pkg.name)) {
+        Action::Rebuild
+    } else {
+        Action::Skip
+    }
+}
+
+impl Plan {
+    pub fn resolved(steps: Vec<Step>) -> Plan {
+        let steps_json: Vec<_> = steps
+            .iter()
+            .map(|s| json!({ "package": s.pkg.name, "version": s.pkg.version, "action": s.action.to_string() }))
+            .collect();
+        events::emit("resolve", json!({ "steps": steps_json }));
+        Plan { steps }
+    }
+
+    pub fn install(targets: &[Package], pkglist: &[Package], with_deps: bool) -> Plan {
+        // with --update-deps, outdated installed packages are updated rather than skipped
+        let force = *FORCE.lock().unwrap();
+        let update_deps = *UPDATE_DEPS.lock().unwrap();
+        let mut seen = HashSet::new();
+
+        let pkgs = if with_deps { resolve_all(targets, pkglist) } else { targets.to_vec() };
+        let mut steps = Vec::new();
+        for pkg in pkgs {
+            let action = if !seen.insert(pkg.name.clone()) {
+                repeated(&steps, &pkg)
+            } else if is_installed(&pkg) && update_deps && !is_current(&pkg) {
+                Action::Update
+            } else if is_installed(&pkg) && !force {
+                Action::Skip
+            } else {
+                Action::Install
+            };
+
+            vpr!("Planned action '{}' for '{}'", action, pkg);
+            steps.push(Step { pkg, action });
+        }
+
+        Plan::resolved(steps)
+    }
+
+    pub fn update(targets: &[Package], pkglist: &[Package], with_deps: bool) -> Plan {
+        let force = *FORCE.lock().unwrap();
+        let mut seen = HashSet::new();
+
+        let pkgs = if with_deps { resolve_all(targets, pkglist) } else { targets.to_vec() };
+        let mut steps = Vec::new();
+        for pkg in pkgs {
+            let action = if !seen.insert(pkg.name.clone()) {
+                repeated(&steps, &pkg)
+            } else if with_deps && !is_installed(&pkg) {
+                Action::Install
+            } else if is_current(&pkg) && !force {
+                Action::Skip
+            } else {
+                Action::Update
+            };
+
+            vpr!("Planned action '{}' for '{}'", action, pkg);
+            steps.push(Step { pkg, action });
+        }
+
+        Plan::resolved(steps)
+    }
+
+    pub fn upgrade(pkglist: &[Package]) -> Plan {
+        // every outdated installed package, in dependency order, respecting holds
+        let held = |p: &Package| CONFIG.upgrade.hold.contains(&p.name);
+        let outdated: Vec<Package> = pkglist
+            .iter()
+            .filter(|p| is_installed(p) && !is_current(p))
+            .cloned()
+            .collect();
+        let targets: Vec<Package> = outdated.iter().filter(|p| !held(p)).cloned().collect();
+        let mut seen = HashSet::new();
+
+        let mut steps = Vec::new();
+        for pkg in resolve_all(&targets, pkglist) {
+            let action = if !seen.insert(pkg.name.clone()) {
+                repeated(&steps, &pkg)
+            } else if !is_installed(&pkg) {
+                Action::Install
+            } else if outdated.contains(&pkg) && held(&pkg) {
+                Action::Hold
+            } else if outdated.contains(&pkg) {
+                Action::Update
+            } else {
+                Action::Skip
+            };
+
+            vpr!("Planned action '{}' for '{}'", action, pkg);
+            steps.push(Step { pkg, action });
+        }
+
+        // surface held packages in the plan even if nothing depends on them
+        for pkg in outdated.into_iter().filter(|p| held(p) && !seen.contains(&p.name)) {
+            steps.push(Step { pkg, action: Action::Hold });
+        }
+
+        Plan::resolved(steps)
+    }
+
+    pub fn rebuild(updated: &[Package], pkglist: &[Package]) -> Plan {
+        // installed dependants of updated packages, in dependency order
+        let dependants = installed_dependants(updated, pkglist);
+        let mut seen = HashSet::new();
+
+        let steps = resolve_all(&dependants, pkglist)
+            .into_iter()
+            .filter(|p| dependants.contains(p) && seen.insert(p.name.clone()))
+            .map(|pkg| Step { pkg, action: Action::Rebuild })
+            .collect();
+
+        Plan::resolved(steps)
+    }
+
+    pub fn get(targets: &[Package]) -> Plan {
+        let steps = targets
+            .iter()
+            .map(|pkg| Step { pkg: pkg.clone(), action: Action::Install })
+            .collect();
+
+        Plan::resolved(steps)
+    }
+
+    pub fn remove(targets: &[Package], pkglist: &[Package], with_deps: bool) -> Plan {
+        // dependants are removed before their dependencies, so resolution order is reversed
+        let pkgs = if with_deps {
+            let mut seen = HashSet::new();
+            let mut pkgs = resolve_all(targets, pkglist);
+            pkgs.retain(|p| seen.insert(p.name.clone()));
+            pkgs.reverse();
+            pkgs
+        } else {
+            targets.to_vec()
+        };
+        let steps = pkgs
+            .into_iter()
+            .map(|pkg| Step { pkg, action: Action::Remove })
+            .collect();
+
+        Plan::resolved(steps)
+    }
+
+    pub fn count(&self, action: Action) -> usize {
+        self.steps.iter().filter(|s| s.action == action).count()
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &Step> {
+        self.steps.iter().filter(|s| !matches!(s.action, Action::Skip | Action::Hold))
+    }
+
+    pub fn display(&self) {
+        let counts: Vec<String> = [
+            (Action::Install, "to install"),
+            (Action::Update,  "to update"),
+            (Action::Rebuild, "to rebuild"),
+            (Action::Remove,  "to remove"),
+            (Action::Hold,    "held"),
+            (Action::Skip,    "already satisfied"),
+        ]
+        .into_iter()
+        .map(|(a, label)| (self.count(a), label))
+        .filter(|(n, _)| *n != 0)
+        .map(|(n, label)| format!("{} {}", n, label))
+        .collect();
+
+        if counts.is_empty() {
+            msg!("Plan: nothing to do");
+            return
+        }
+
+        msg!("Plan: {}", counts.join(", "));
+        for step in self.steps.iter().filter(|s| s.action != Action::Skip) {
+            match step.action {
+                Action::Install | Action::Update | Action::Rebuild => println!("  {}{}", step, annotate(&step.pkg)),
+                _ => println!("  {}", step),
+            }
+        }
+
+        let builds: Vec<&Package> = self.pending()
+            .filter(|s| s.action != Action::Remove)
+            .map(|s| &s.pkg)
+            .collect();
+        if let Some(t) = total(&builds) {
+            msg!("Estimated build time: ≈ {}", human(t));
+        }
+    }
+
+    pub fn downloads(&self) -> Vec<(String, String)> {
+        // (file name, url) pairs missing from $RIDSOURCES
+        let force = *FORCE.lock().unwrap();
+        let mut downloads = Vec::new();
+
+        for step in self.pending().filter(|s| s.action != Action::Remove) {
+            let p = &step.pkg;
+            for url in p.downloads.iter() {
+                let file_name = url.split('/').next_back().unwrap_or_default().to_string();
+                downloads.push((file_name, url.clone()));
+            }
+
+            if !p.link.is_empty() {
+                downloads.push((format!("{}.tar", p), p.link.clone()));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        downloads.retain(|(f, _)| seen.insert(f.clone()) && (force || !SOURCES.join(f).exists()));
+        downloads
+    }
+
+    pub fn pretend(&self, pkglist: &[Package]) {
+        msg!("Pretending; nothing will be built, removed, or recorded");
+        self.display();
+
+        let removed: Vec<Package> = self.pending()
+            .filter(|s| s.action == Action::Remove)
+            .map(|s| s.pkg.clone())
+            .collect();
+
+        if !removed.is_empty() {
+            let dependants = installed_dependants(&removed, pkglist);
+
+            if !dependants.is_empty() {
+                erm!("Removal would prompt for {} dependant packages:", dependants.len());
+                display_list(&dependants);
+            }
+        }
+
+        for step in self.pending() {
+            for when in [When::Pre, When::Post] {
+                for hook in hooks::matching(when, step, pkglist) {
+                    msg!("Would run {} hook '{}' for '{}'", when, hook.name, step.pkg);
+                }
+            }
+        }
+
+        if self.pending().any(|s| s.action != Action::Remove) {
+            self.show_downloads();
+        }
+    }
+
+    pub fn show_downloads(&self) {
+        let downloads = self.downloads();
+        if downloads.is_empty() {
+            msg!("Nothing to download");
+            return
+        }
+
+        msg!("Files to download");
+        let mut total = 0;
+        for (file_name, url) in downloads {
+            let size = ureq::head(&url)
+                .call()
+                .ok()
+                .and_then(|r| r.header("Content-Length").and_then(|l| l.parse::<u64>().ok()));
+
+            match size {
+                Some(s) => {
+                    total += s;
+                    pr!("  {:<40} {:>10}", file_name, HumanBytes(s).to_string());
+                }
+                None => pr!("  {:<40} {:>10}", file_name, "unknown"),
+            }
+        }
+        msg!("Total download size: {}", HumanBytes(total));
+    }
+}
@@ -3,26 +3,102 @@
 // package manager struct
 
 use crate::cmd::exec;
-use crate::core::{confirm_removal, download, fetch, mint, prune_sources, remove_tarballs};
-use crate::flags::FORCE;
+use crate::config::CONFIG;
+use crate::core::{confirm_removal, download, fetch, mint, prune_sources, remove_tarballs, BuildError, Job};
+use crate::events;
+use crate::flags::{ALL, DEPTH, FORCE, KEEP_GOING, PARALLEL, PRETEND, REBUILD_DEPENDANTS, TREE};
+use crate::graph::{export, tree, Direction};
+use crate::hooks::{self, When};
 use crate::linkval::validate;
-use crate::package::Package;
+use crate::log;
+use crate::package::{BuildOutcome, BuildRecord, Package, PackageStatus, TestOutcome};
 use crate::paths::BIN;
-use crate::resolve::{resolve_deps, find_dependants, deep_dependants};
+use crate::plan::{is_current, Action, Plan, Step, Summary};
+use crate::resolve::{dependency_paths, installed_dependants, resolve_deps, reverse_deps};
+use crate::signals;
+use crate::timing::{annotate, human, save_sbu, Timing};
 use crate::tracking;
+use crate::transaction;
+use crate::triggers;
 use crate::upstream::check_upstream;
-use crate::utils::{dedup, display_list, do_install};
+use crate::utils::{dedup, display_list};
 use crate::{die, vpr, pr, yn, msg, erm};
-use indicatif::{ProgressStyle, ProgressBar};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde_json::json;
+use std::collections::HashSet;
+use std::process::exit;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 pub struct PM {
     pub pkgs: Vec<Package>,
     pub pkglist: Vec<Package>,
+    pub queued: Vec<Step>,
+    pub fired: HashSet<(String, String)>,
+    pub triggering: bool,
+}
+
+fn build(step: &Step, job: &Job) -> Result<Timing, BuildError> {
+    // fetches and runs a step without touching the package list
+    let pkg = &step.pkg;
+    events::emit("build_start", json!({
+        "package": pkg.name, "version": pkg.version, "action": step.action.to_string(), "log": job.log
+    }));
+    match step.action {
+        Action::Install | Action::Rebuild => {
+            fetch(pkg, job)?;
+            let timing = mint('i', pkg, job)?;
+            job.merge()?;
+            Ok(timing)
+        }
+        Action::Update => {
+            fetch(pkg, job)?;
+            let timing = mint('u', pkg, job)?;
+            job.merge()?;
+            Ok(timing)
+        }
+        Action::Remove => mint('r', pkg, job),
+        Action::Hold | Action::Skip => Ok(Timing::default()),
+    }
+}
+
+fn ended(step: &Step, job: &Job, result: &Result<Timing, BuildError>, tests: Option<TestOutcome>) {
+    let pkg = &step.pkg;
+    let (outcome, error) = match result {
+        Ok(_) => (BuildOutcome::Succeeded, None),
+        Err(e) if signals::interrupted().is_some() => (BuildOutcome::Interrupted, Some(e)),
+        Err(e) => (BuildOutcome::Failed, Some(e)),
+    };
+    let timing = result.as_ref().ok();
+
+    events::emit("build_end", json!({
+        "package": pkg.name,
+        "version": pkg.version,
+        "action": step.action.to_string(),
+        "outcome": outcome,
+        "phase": error.map(|e| e.phase.to_string()),
+        "code": error.and_then(|e| e.code),
+        "signal": error.and_then(|e| e.signal),
+        "wall": timing.map(|t| t.wall.as_secs_f64()),
+        "cpu": timing.map(|t| t.cpu.as_secs_f64()),
+        "tests": tests,
+        "log": job.log,
+    }));
+}
+
+fn finished(step: &Step) {
+    match step.action {
+        Action::Install | Action::Rebuild => msg!("Installed '{}'", step.pkg),
+        Action::Update => msg!("Updated to '{}'", step.pkg),
+        Action::Remove => msg!("Removed '{}'", step.pkg),
+        Action::Hold | Action::Skip => (),
+    }
 }
 
 impl PM {
     pub fn new(pkgs: Vec<Package>, pkglist: Vec<Package>) -> Self {
-        PM { pkgs, pkglist }
+        PM { pkgs, pkglist, queued: Vec::new(), fired: HashSet::new(), triggering: false }
     }
 
     pub fn list(&self) {
@@ -36,13 +112,12 @@ impl PM {
                 .collect()
         };
 
-        if displayed.len() != self.pkgs.len() {
-            if let Some(pkg) = self.pkgs
+        if displayed.len() != self.pkgs.len()
+            && let Some(pkg) = self.pkgs
                 .iter()
                 .find(|pkg| !displayed.iter().any(|p| p.name == *pkg.name))
-            {
-                die!("Package '{}' missing from pkglist", pkg)
-            }
+        {
+            die!("Package '{}' missing from pkglist", pkg)
         }
 
         displayed.sort();
@@ -79,165 +154,652 @@ impl PM {
 
     pub fn dependencies(&self) {
         for pkg in self.pkgs.iter() {
+            if *TREE.lock().unwrap() {
+                msg!("Dependency tree for {}", pkg);
+                tree(pkg, &self.pkglist, Direction::Dependencies);
+                continue
+            }
+
             let d = resolve_deps(pkg, &self.pkglist);
             msg!("Dependencies for {}", pkg);
             display_list(&d);
         }
     }
 
-    // I don't like how this uses the force flag, but it's the best solution I
-    // can think of for adding deep_dependants() functionality
     pub fn dependants(&self) {
+        let depth = if *ALL.lock().unwrap() { usize::MAX } else { DEPTH.lock().unwrap().unwrap_or(1) };
+
         for pkg in self.pkgs.iter() {
+            if *TREE.lock().unwrap() {
+                msg!("Dependant tree for {}", pkg);
+                tree(pkg, &self.pkglist, Direction::Dependants);
+                continue
+            }
 
-            if *FORCE.lock().unwrap() {
-                let mut all_dependants: Vec<Package> = Vec::new();
-                let deps = resolve_deps(pkg, &self.pkglist);
-                for dep in deps.iter() {
-                    let d = find_dependants(dep, self.pkglist.clone());
-                    all_dependants.extend(d);
-                }
-                let all_dependants = dedup(all_dependants);
-                msg!("Deep dependants for {}", pkg);
-                display_list(&all_dependants);
-                return
+            let d = reverse_deps(std::slice::from_ref(pkg), &self.pkglist, depth);
+            match depth {
+                1 => msg!("Direct dependants for {}", pkg),
+                usize::MAX => msg!("All dependants for {}", pkg),
+                n => msg!("Dependants for {} (depth {})", pkg, n),
             }
 
-            let d = find_dependants(pkg, self.pkglist.clone());
-            msg!("Direct dependants for {}", pkg);
-            display_list(&d);
-            vpr!("Tip: Use -fD for deep dependants")
+            let d: Vec<Package> = d.into_iter().map(|(p, _)| p).collect();
+            display_list(&dedup(d));
+            if depth == 1 { vpr!("Tip: Use -D --all for all dependants") }
+        }
+    }
+
+    pub fn why(&self, args: &[String]) {
+        let pkg = Package::new(&args[0], self.pkglist.clone());
+
+        // roots are explicitly installed packages, or installed packages nothing installed depends on
+        let installed: Vec<&Package> = self.pkglist
+            .iter()
+            .filter(|p| matches!(p.status, PackageStatus::Installed))
+            .collect();
+        let roots: Vec<Package> = match args.get(1) {
+            Some(target) => vec![Package::new(target, self.pkglist.clone())],
+            None => installed
+                .iter()
+                .filter(|p| p.explicit || !installed.iter().any(|i| i.deps.contains(&p.name)))
+                .map(|p| (*p).clone())
+                .collect(),
+        };
+
+        let mut paths: Vec<Vec<String>> = roots
+            .iter()
+            .filter(|r| r.name != pkg.name)
+            .flat_map(|r| dependency_paths(r, &pkg.name, &self.pkglist))
+            .collect();
+        paths.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+
+        if paths.is_empty() {
+            match args.get(1) {
+                Some(target) => msg!("'{}' does not depend on '{}'", target, pkg.name),
+                None => msg!("'{}' is not required by any installed package", pkg.name),
+            }
+            return
         }
+
+        msg!("Why '{}' is needed ({} paths)", pkg.name, paths.len());
+        for path in paths {
+            pr!("  {}", path.join(" -> "));
+        }
+    }
+
+    pub fn graph(&self, format: &str) {
+        export(&self.pkgs, &self.pkglist, format)
     }
 
     pub fn get(&self) {
+        if *PRETEND.lock().unwrap() {
+            Plan::get(&self.pkgs).show_downloads();
+            return
+        }
+
         for pkg in self.pkgs.clone() {
             msg!("Getting files for {}", pkg);
-            download(pkg, *FORCE.lock().unwrap());
+            if let Err(e) = download(pkg, *FORCE.lock().unwrap()) {
+                die!("{}", e)
+            }
         }
     }
 
-    pub fn install(&mut self) {
-        for pkg in self.pkgs.iter() {
-            if do_install(pkg) {
-                fetch(pkg);
-                mint('i', pkg);
-                tracking::add(&mut self.pkglist, pkg);
-                msg!("Installed '{}'", pkg);
+    fn commit(&mut self, step: &Step, job: &Job, result: &Result<Timing, BuildError>) -> bool {
+        // records the outcome of a built step, returning whether it succeeded
+        let pkg = &step.pkg;
+        let tests = job.tests();
+        if let Some(t) = tests {
+            log::append(&job.log, &format!("# tests: {}", t));
+            if t == TestOutcome::Failed { erm!("Tests failed for '{}'; see '{}'", pkg, job.log.display()) }
+        }
+        log::finish(&job.log, result);
+        ended(step, job, result, tests);
+        log::prune(&pkg.name);
+        job.clean();
+
+        match (&step.action, result) {
+            (Action::Remove, Ok(_)) => {
+                tracking::rem(&mut self.pkglist, pkg);
+                remove_tarballs(&pkg.name);
+                return true
+            }
+            (_, Ok(_)) => {
+                let explicit = self.pkgs.contains(pkg);
+                tracking::add(&mut self.pkglist, pkg, explicit);
             }
+            _ => (),
         }
+
+        let log = job.log.display().to_string();
+        let record = match result {
+            Ok(t) => BuildRecord {
+                version: pkg.version.clone(),
+                log,
+                wall: t.wall.as_secs_f64(),
+                cpu: t.cpu.as_secs_f64(),
+                env: job.env.clone(),
+                tests,
+                ..Default::default()
+            },
+            Err(e) if signals::interrupted().is_some() => {
+                // an interrupted build tree is useless, so it's removed
+                erm!("Interrupted while trying to {} '{}'", step.action, pkg);
+                job.scrap(pkg);
+                BuildRecord {
+                    version: pkg.version.clone(),
+                    outcome: BuildOutcome::Interrupted,
+                    phase: e.phase.to_string(),
+                    log,
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                erm!("Failed to {} '{}': {}", step.action, pkg, e);
+                erm!("See '{}'", log);
+                BuildRecord {
+                    version: pkg.version.clone(),
+                    outcome: BuildOutcome::Failed,
+                    phase: e.phase.to_string(),
+                    code: e.code,
+                    signal: e.signal,
+                    log,
+                    env: job.env.clone(),
+                    tests,
+                    ..Default::default()
+                }
+            }
+        };
+
+        tracking::record(&mut self.pkglist, pkg, record);
+        result.is_ok()
     }
 
-    pub fn install_with_dependencies(&mut self) {
-        for pkg in self.pkgs.iter() {
-            let deps = resolve_deps(pkg, &self.pkglist);
-            msg!("Dependencies for '{}'", pkg);
-            display_list(&deps);
-            for dep in deps.iter() {
-                if do_install(dep) {
-                    fetch(dep);
-                    mint('i', dep);
-                    tracking::add(&mut self.pkglist, pkg);
-                    msg!("Installed '{}'", pkg);
+    fn hooks(&mut self, when: When, step: &Step, job: &Job) {
+        // each hook fires at most once per package per run, so hooks can't loop
+        for hook in hooks::matching(when, step, &self.pkglist) {
+            let key = (format!("{}:{}", when, hook.name), step.pkg.name.clone());
+            if !self.fired.insert(key) { continue }
+
+            for (name, action) in hooks::run(hook, when, step, job) {
+                if self.queued.iter().any(|s| s.pkg.name == name && s.action == action) { continue }
+
+                let pkg = Package::new(&name, self.pkglist.clone());
+                if action == Action::Update && is_current(&pkg) && !*FORCE.lock().unwrap() {
+                    vpr!("Not queueing update for '{}' since it's up to date", pkg);
+                    continue
                 }
+
+                vpr!("Hook '{}' queued {} for '{}'", hook.name, action, pkg);
+                self.queued.push(Step { pkg, action });
             }
         }
     }
 
-    pub fn update(&mut self) {
-        for pkg in self.pkgs.iter() {
-            if pkg.installed_version == pkg.version 
-            && !*FORCE.lock().unwrap() 
-            && pkg.version != "9999" 
-            {
-                msg!("Package '{}' up to date", pkg);
-                return;
+    fn begin_triggers(&mut self) -> bool {
+        // only the outermost plan of a run evaluates triggers
+        if self.triggering { return false }
+        self.triggering = true;
+        triggers::reset();
+        true
+    }
+
+    fn run_triggers(&mut self) {
+        if self.triggering {
+            self.triggering = false;
+            triggers::run()
+        }
+    }
+
+    fn run_queued(&mut self) {
+        // runs the actions queued by hooks as a follow-up plan
+        if self.queued.is_empty() { return }
+
+        let plan = Plan { steps: std::mem::take(&mut self.queued) };
+        msg!("Running actions queued by hooks");
+        plan.display();
+        self.execute_or_exit(&plan);
+    }
+
+    fn step(&mut self, step: &Step) -> bool {
+        // runs a step in the shared build directories
+        let pkg = &step.pkg;
+        let builds = matches!(step.action, Action::Install | Action::Update | Action::Rebuild);
+        let job = Job::shared(pkg, log::create(&pkg.name, &pkg.to_string(), &step.to_string()), builds);
+        self.hooks(When::Pre, step, &job);
+
+        match step.action {
+            Action::Install | Action::Rebuild => msg!("Installing {}{}", pkg, annotate(pkg)),
+            Action::Update => msg!("Updating to '{}'...{}", pkg, annotate(pkg)),
+            _ => (),
+        }
+
+        let result = build(step, &job);
+        let ok = self.commit(step, &job, &result);
+        signals::exit_if_interrupted();
+
+        if ok {
+            finished(step);
+            self.hooks(When::Post, step, &job);
+        }
+        ok
+    }
+
+    fn execute(&mut self, plan: &Plan) -> Summary {
+        // runs a plan, saving progress so an interrupted plan can be resumed
+        if let Some(t) = transaction::load() {
+            erm!("An interrupted transaction has {} steps remaining", t.remaining().count());
+            if !yn!("Discard it and continue?", false) {
+                msg!("Use --resume to continue it or --abort to discard it");
+                exit(1)
             }
+        }
 
-            msg!("Updating to '{}'...", pkg);
-            fetch(pkg);
-            mint('u', pkg);
+        transaction::begin(plan, &self.pkgs);
+        let owner = self.begin_triggers();
+        let jobs = PARALLEL.lock().unwrap().unwrap_or(CONFIG.build.jobs);
+        let mut summary = if jobs > 1 && !plan.steps.iter().any(|s| s.action == Action::Remove) {
+            self.execute_parallel(plan, jobs)
+        } else {
+            self.execute_serial(plan)
+        };
+
+        summary.logs = summary.failed
+            .iter()
+            .filter_map(|s| self.pkglist.iter().find(|p| p.name == s.pkg.name))
+            .filter_map(|p| p.build.as_ref().map(|b| (p.name.clone(), b.log.clone())))
+            .collect();
 
-            tracking::add(&mut self.pkglist, pkg);
-            msg!("Updated to '{}'", pkg);
+        if summary.failed.is_empty() {
+            transaction::clear();
+            self.run_queued();
+        } else {
+            msg!("Fix the problem and run rid --resume, or discard the rest with rid --abort");
         }
+
+        if owner { self.run_triggers() }
+        summary
     }
 
-    pub fn update_with_dependencies(&mut self) {
-        for pkg in self.pkgs.iter() {
-            let deps = resolve_deps(pkg, &self.pkglist);
-            msg!("Dependencies for '{}'", pkg);
-            display_list(&deps);
-            for dep in deps.iter() {
-                if dep.installed_version == dep.version
-                && !*FORCE.lock().unwrap() 
-                && pkg.version != "9999" 
-                {
-                    msg!("Package '{}' up to date", dep);
-                    return;
+    fn execute_serial(&mut self, plan: &Plan) -> Summary {
+        // stops at the first failure unless --keep-going is passed, in which case
+        // only the steps depending on a failure are skipped
+        let keep_going = *KEEP_GOING.lock().unwrap();
+        let mut blocked: HashSet<String> = HashSet::new();
+        let mut summary = Summary::default();
+        let mut steps = plan.steps.iter().enumerate();
+
+        for (i, step) in steps.by_ref() {
+            let pkg = &step.pkg;
+            match step.action {
+                Action::Hold => summary.skipped.push(step.clone()),
+                Action::Skip => (),
+                _ if blocked.contains(&pkg.name) || pkg.deps.iter().any(|d| blocked.contains(d)) => {
+                    vpr!("Skipping '{}' since a dependency failed", pkg);
+                    blocked.insert(pkg.name.clone());
+                    summary.skipped.push(step.clone())
+                }
+                _ if self.step(step) => {
+                    transaction::done(i);
+                    summary.succeeded.push(step.clone())
+                }
+                _ => {
+                    summary.failed.push(step.clone());
+                    if !keep_going { break }
+                    blocked.insert(pkg.name.clone());
+                }
+            }
+        }
+
+        summary.remaining = steps
+            .map(|(_, s)| s)
+            .filter(|s| !matches!(s.action, Action::Skip | Action::Hold))
+            .cloned()
+            .collect();
+        summary
+    }
+
+    fn execute_parallel(&mut self, plan: &Plan, jobs: usize) -> Summary {
+        // runs up to `jobs` steps at once, starting a step once the earlier steps it
+        // depends on (including an earlier build of the same package) have succeeded
+        #[derive(Clone, Copy, PartialEq)]
+        enum State { Waiting, Running, Done, Failed, Skipped }
+
+        let keep_going = *KEEP_GOING.lock().unwrap();
+
+        let mut summary = Summary {
+            skipped: plan.steps.iter().filter(|s| s.action == Action::Hold).cloned().collect(),
+            ..Default::default()
+        };
+
+        let (index, steps): (Vec<usize>, Vec<&Step>) = plan.steps
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !matches!(s.action, Action::Skip | Action::Hold))
+            .unzip();
+        let waits: Vec<Vec<usize>> = steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                (0..i)
+                    .filter(|&j| steps[j].pkg.name == s.pkg.name || s.pkg.deps.contains(&steps[j].pkg.name))
+                    .collect()
+            })
+            .collect();
+
+        // downloads are done up front, one at a time
+        for step in steps.iter() {
+            if let Err(e) = download(step.pkg.clone(), false) {
+                vpr!("Deferring download failure for '{}': {}", step.pkg, e);
+            }
+        }
+        signals::exit_if_interrupted();
+
+        msg!("Building {} packages with up to {} jobs", steps.len(), jobs);
+        let mp = MultiProgress::new();
+        let style = ProgressStyle::with_template("{spinner:.red} [{elapsed_precise}] {prefix:.bold} {wide_msg}")
+            .unwrap();
+
+        let mut state = vec![State::Waiting; steps.len()];
+        let (tx, rx) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let mut running = 0;
+            let mut failed = false;
+
+            loop {
+                // with --keep-going, steps depending on a failure are skipped
+                for i in 0..steps.len() {
+                    if state[i] == State::Waiting
+                        && waits[i].iter().any(|&j| matches!(state[j], State::Failed | State::Skipped))
+                    {
+                        vpr!("Skipping '{}' since a dependency failed", steps[i].pkg);
+                        state[i] = State::Skipped;
+                        summary.skipped.push(steps[i].clone());
+                    }
+                }
+
+                for i in 0..steps.len() {
+                    if failed || running >= jobs || signals::interrupted().is_some() { break }
+                    if state[i] != State::Waiting || !waits[i].iter().all(|&j| state[j] == State::Done) {
+                        continue
+                    }
+
+                    let step = steps[i];
+                    let bar = mp.add(ProgressBar::new_spinner());
+                    bar.set_style(style.clone());
+                    bar.set_prefix(format!("{}{}", step, annotate(&step.pkg)));
+                    bar.enable_steady_tick(Duration::from_millis(120));
+
+                    let log = log::create(&step.pkg.name, &step.pkg.to_string(), &step.to_string());
+                    let job = Job::isolated(&step.pkg, log, bar);
+                    mp.suspend(|| self.hooks(When::Pre, step, &job));
+                    let tx = tx.clone();
+
+                    state[i] = State::Running;
+                    running += 1;
+                    scope.spawn(move || {
+                        let result = build(step, &job);
+                        let _ = tx.send((i, job, result));
+                    });
+                }
+
+                if running == 0 { break }
+                let (i, job, result) = rx.recv().unwrap();
+                running -= 1;
+
+                if let Some(bar) = &job.bar { bar.finish_and_clear() }
+
+                let ok = mp.suspend(|| {
+                    let ok = self.commit(steps[i], &job, &result);
+                    if ok {
+                        finished(steps[i]);
+                        self.hooks(When::Post, steps[i], &job);
+                    }
+                    ok
+                });
+
+                if ok {
+                    transaction::done(index[i]);
+                    state[i] = State::Done;
+                    summary.succeeded.push(steps[i].clone());
+                } else {
+                    failed = !keep_going;
+                    state[i] = State::Failed;
+                    summary.failed.push(steps[i].clone());
                 }
+            }
+        });
+
+        // running builds were interrupted too, and have been recorded by now
+        signals::exit_if_interrupted();
+
+        summary.remaining = steps
+            .iter()
+            .zip(state.iter())
+            .filter(|(_, s)| **s == State::Waiting)
+            .map(|(step, _)| (*step).clone())
+            .collect();
+        summary
+    }
+
+    fn execute_or_exit(&mut self, plan: &Plan) {
+        let summary = self.execute(plan);
+        if !summary.failed.is_empty() {
+            if *KEEP_GOING.lock().unwrap() || !summary.remaining.is_empty() { summary.display() }
+            exit(1)
+        }
+    }
+
+    pub fn install(&mut self) {
+        let plan = Plan::install(&self.pkgs, &self.pkglist, false);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
+
+        self.begin_triggers();
+        for step in plan.steps.iter() {
+            if step.action == Action::Skip {
+                msg!("{} is already installed", step.pkg);
+                continue
+            }
+            if !self.step(step) {
+                self.run_triggers();
+                exit(1)
+            }
+        }
+
+        self.run_queued();
+        self.run_triggers();
+    }
+
+    pub fn install_with_dependencies(&mut self) {
+        let plan = Plan::install(&self.pkgs, &self.pkglist, true);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
+
+        plan.display();
+        self.execute_or_exit(&plan);
+    }
+
+    fn rebuild_dependants(&mut self, plan: &Plan) {
+        if !*REBUILD_DEPENDANTS.lock().unwrap() { return }
+
+        let updated: Vec<Package> = plan.steps
+            .iter()
+            .filter(|s| s.action == Action::Update)
+            .map(|s| s.pkg.clone())
+            .collect();
+        if updated.is_empty() { return }
+
+        let rebuilds = Plan::rebuild(&updated, &self.pkglist);
+        if rebuilds.steps.is_empty() {
+            msg!("No installed dependants to rebuild");
+            return
+        }
+
+        msg!("Installed dependants of updated packages");
+        rebuilds.display();
+        if *PRETEND.lock().unwrap() { return }
+
+        let message = format!("Rebuild {} dependants?", rebuilds.steps.len());
+        if !yn!(&message, true) {
+            vpr!("Not rebuilding dependants since 'n' was selected");
+            return
+        }
+
+        self.execute_or_exit(&rebuilds);
+    }
 
-                msg!("Updating to '{}'...", dep);
-                fetch(dep);
-                mint('u', dep);
+    pub fn update(&mut self) {
+        let plan = Plan::update(&self.pkgs, &self.pkglist, false);
+        if *PRETEND.lock().unwrap() {
+            plan.pretend(&self.pkglist);
+            return self.rebuild_dependants(&plan)
+        }
 
-                tracking::add(&mut self.pkglist, dep);
-                msg!("Updated to '{}'", dep);
+        self.begin_triggers();
+        for step in plan.steps.iter() {
+            if step.action == Action::Skip {
+                msg!("Package '{}' up to date", step.pkg);
+                continue
+            }
+            if !self.step(step) {
+                self.run_triggers();
+                exit(1)
             }
         }
+
+        self.run_queued();
+        self.rebuild_dependants(&plan);
+        self.run_triggers();
+    }
+
+    pub fn update_with_dependencies(&mut self) {
+        let plan = Plan::update(&self.pkgs, &self.pkglist, true);
+        if *PRETEND.lock().unwrap() {
+            plan.pretend(&self.pkglist);
+            return self.rebuild_dependants(&plan)
+        }
+
+        plan.display();
+        self.execute_or_exit(&plan);
+
+        self.rebuild_dependants(&plan);
+    }
+
+    pub fn upgrade(&mut self) {
+        let plan = Plan::upgrade(&self.pkglist);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
+
+        plan.display();
+        if plan.pending().next().is_none() { return }
+
+        let summary = self.execute(&plan);
+        summary.display();
+    }
+
+    pub fn calibrate(&mut self) {
+        // times a build of the reference package and stores it as one sbu
+        let pkg = match self.pkgs.first() {
+            Some(p) => p.clone(),
+            None => Package::new(&CONFIG.build.reference, self.pkglist.clone()),
+        };
+
+        if *PRETEND.lock().unwrap() {
+            msg!("Would calibrate 1 SBU by building '{}'", pkg);
+            return
+        }
+
+        msg!("Calibrating 1 SBU by building '{}'", pkg);
+        let step = Step { pkg, action: Action::Install };
+        if !self.step(&step) { exit(1) }
+
+        let wall = self.pkglist
+            .iter()
+            .find(|p| p.name == step.pkg.name)
+            .and_then(|p| p.build.as_ref())
+            .map_or(0.0, |b| b.wall);
+        if wall <= 0.0 { die!("No build time was recorded for '{}'", step.pkg) }
+
+        let sbu = Duration::from_secs_f64(wall);
+        save_sbu(sbu);
+        msg!("1 SBU = {}", human(sbu));
+    }
+
+    pub fn resume(&mut self) {
+        let Some(t) = transaction::load() else { die!("No interrupted transaction to resume") };
+
+        self.pkgs = t.explicit.iter().map(|n| Package::new(n, self.pkglist.clone())).collect();
+        let plan = t.plan(&self.pkglist);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
+
+        msg!("Resuming interrupted transaction");
+        plan.display();
+        transaction::clear();
+        self.execute_or_exit(&plan);
+    }
+
+    pub fn abort(&mut self) {
+        let Some(t) = transaction::load() else { die!("No interrupted transaction to abort") };
+
+        if *PRETEND.lock().unwrap() {
+            msg!("Would discard interrupted transaction ({} steps remaining)", t.remaining().count());
+            return
+        }
+
+        transaction::clear();
+        msg!("Discarded interrupted transaction ({} steps remaining)", t.remaining().count());
     }
 
     pub fn remove(&mut self) {
-        for pkg in self.pkgs.iter() {
-            if !confirm_removal(pkg, &self.pkglist) {
+        let plan = Plan::remove(&self.pkgs, &self.pkglist, false);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
+
+        self.begin_triggers();
+        for step in plan.steps.iter() {
+            if !confirm_removal(&step.pkg, &self.pkglist) {
+                self.run_triggers();
                 return
             }
-
-            mint('r', pkg);
-            tracking::rem(&mut self.pkglist, pkg);
-            remove_tarballs(&pkg.name);
-            msg!("Removed '{}'", pkg);
+            if !self.step(step) {
+                self.run_triggers();
+                exit(1)
+            }
         }
+
+        self.run_queued();
+        self.run_triggers();
     }
 
     pub fn remove_with_dependencies(&mut self) {
         // recursively removes a package and all its dependencies
         // this can be very dangerous
-        let force = *FORCE.lock().unwrap();
-        for pkg in self.pkgs.iter() {
-            if force {
-                erm!("WARNING: Skipping all checks for deep dependants of '{}'", pkg)
-            } else {
-                vpr!("Checking for deep dependants of '{}'", pkg)
-            }
+        let plan = Plan::remove(&self.pkgs, &self.pkglist, true);
+        if *PRETEND.lock().unwrap() { return plan.pretend(&self.pkglist) }
 
-            let deps = resolve_deps(pkg, &self.pkglist);
-            msg!("Depencies for '{}'", pkg);
-            display_list(&deps);
-
-            if !force {
-                let dependants = deep_dependants(&deps, &self.pkglist);
-                let len = dependants.len();
-                if len != 0 {
-                    erm!("Found {} dependant packages:", len);
-                    display_list(&dependants);
-                    let message = format!(
-                    "Remove '{}' and its dependencies ({} total dependants)?",
-                    pkg.name, len);
-                    if !yn!(&message, false) {
-                        vpr!("Aborting removal since 'n' was selected");
-                        return;
-                    }
+        plan.display();
+        let force = *FORCE.lock().unwrap();
+        if force {
+            erm!("WARNING: Skipping all checks for deep dependants")
+        } else {
+            vpr!("Checking for deep dependants");
+
+            let deps: Vec<Package> = plan.steps.iter().map(|s| s.pkg.clone()).collect();
+            let dependants = installed_dependants(&deps, &self.pkglist);
+
+            let len = dependants.len();
+            if len != 0 {
+                erm!("Found {} dependant packages:", len);
+                display_list(&dependants);
+                let message = format!(
+                "Remove {} packages ({} total dependants)?",
+                deps.len(), len);
+                if !yn!(&message, false) {
+                    vpr!("Aborting removal since 'n' was selected");
+                    return;
                 }
             }
-
-            for dep in deps.iter() {
-                mint('r', dep);
-                tracking::rem(&mut self.pkglist, dep);
-                remove_tarballs(&dep.name);
-                msg!("Removed '{}'", dep);
-            }
         }
+
+        self.execute_or_exit(&plan);
     }
 
     pub fn news(&mut self) {
@@ -326,9 +888,17 @@ impl PM {
 
     // I'd like to enable support for syncing individual repos at some point in the future
     pub fn sync(&self) {
+        if *PRETEND.lock().unwrap() {
+            msg!("Would sync repos in $RIDMETA");
+            return
+        }
+
         let command = format!("{}/sy", BIN.display());
-        if let Err(e) = exec(&command) {
-            die!("Failed to sync repos: {}", e)
+        let log = log::create("sync", "sync", "sync");
+        match exec(&command, &log, None) {
+            Ok((status, _)) if status.success() => (),
+            Ok((status, _)) => die!("Failed to sync repos: {}", status),
+            Err(e) => die!("Failed to sync repos: {}", e),
         }
     }   
 }
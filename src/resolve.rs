@@ -2,50 +2,210 @@
 //
 // responsible for dependency/dependant resolution
 
-use crate::package::Package;
+use crate::flags::FORCE;
+use crate::package::{Package, PackageStatus};
 use crate::utils::dedup;
-use crate::vpr;
-use std::collections::HashSet;
+use crate::{die, erm, vpr};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+lazy_static! {
+    // (package, dependency) edges which may be skipped to break a cycle, as
+    // annotated in sets with 'package ~dependency'
+    pub static ref BOOTSTRAP: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+}
+
+pub fn is_bootstrap(pkg: &Package, dep: &str) -> bool {
+    pkg.bootstrap.iter().any(|b| b == dep)
+    || BOOTSTRAP.lock().unwrap().contains(&(pkg.name.clone(), dep.to_string()))
+}
+
+fn reaches(from: &str, to: &str, pkglist: &[Package]) -> bool {
+    let mut seen = HashSet::new();
+    let mut stack = vec![from.to_string()];
+
+    while let Some(name) = stack.pop() {
+        if name == to { return true }
+        if !seen.insert(name.clone()) { continue }
+
+        let p = Package::new(&name, pkglist.to_vec());
+        stack.extend(p.deps);
+    }
+
+    false
+}
+
+#[derive(Default)]
+struct Resolution {
+    resolved: HashSet<String>,
+    visiting: Vec<String>,
+    rebuilds: HashMap<String, Vec<String>>, // dependency -> packages to rebuild once it's built
+    order: Vec<String>,
+}
+
+impl Resolution {
+    fn push(&mut self, name: &str) {
+        self.resolved.insert(name.to_string());
+        self.order.push(name.to_string());
+
+        for r in self.rebuilds.remove(name).unwrap_or_default() {
+            vpr!("Rebuilding '{}' after '{}'", r, name);
+            self.order.push(r);
+        }
+    }
+}
+
+fn deep_deps(pkg: &Package, pkglist: &[Package], res: &mut Resolution) {
+    res.visiting.push(pkg.name.clone());
+    let mut deferred = Vec::new();
 
-fn deep_deps(pkg: &Package, pkglist: &Vec<Package>, resolved: &mut HashSet<String>, order: &mut Vec<String>) {
     for dep in &pkg.deps {
-        if !resolved.contains(dep) {
-            resolved.insert(dep.clone());
+        if res.resolved.contains(dep) { continue }
 
-            let d = Package::new(dep, pkglist.to_vec());
-            deep_deps(&d, pkglist, resolved, order);
+        if is_bootstrap(pkg, dep) && reaches(dep, &pkg.name, pkglist) {
+            vpr!("Building '{}' without '{}' first to break a cycle", pkg.name, dep);
+            res.rebuilds.entry(dep.clone()).or_default().push(pkg.name.clone());
+            deferred.push(dep.clone());
+            continue
+        }
+
+        if let Some(pos) = res.visiting.iter().position(|v| v == dep) {
+            let mut cycle = res.visiting[pos..].to_vec();
+            cycle.push(dep.clone());
+            erm!("Dependency cycle: {}", cycle.join(" -> "));
+
+            if !*FORCE.lock().unwrap() {
+                die!("Unresolvable dependency cycle; annotate a bootstrap dependency with $BOOT or '~' in a set")
+            }
+
+            erm!("WARNING: Ignoring edge '{} -> {}'", pkg.name, dep);
+            continue
+        }
+
+        let d = Package::new(dep, pkglist.to_vec());
+        deep_deps(&d, pkglist, res);
+    }
+
+    res.visiting.pop();
+    res.push(&pkg.name);
+
+    for dep in deferred {
+        if !res.resolved.contains(&dep) && !res.visiting.contains(&dep) {
+            let d = Package::new(&dep, pkglist.to_vec());
+            deep_deps(&d, pkglist, res);
         }
     }
-    order.push(pkg.name.clone());
 }
 
-pub fn resolve_deps(pkg: &Package, pkglist: &Vec<Package>) -> Vec<Package> {
-    let mut resolved = HashSet::new();
-    let mut order = Vec::new();
-    deep_deps(pkg, pkglist, &mut resolved, &mut order);
+pub fn resolve_all(pkgs: &[Package], pkglist: &[Package]) -> Vec<Package> {
+    // resolves several targets into one order, so shared dependencies appear
+    // once and targets are ordered relative to each other
+    //
+    // the returned order may contain a package twice if it has to be rebuilt
+    // after a bootstrap dependency
+    let mut res = Resolution::default();
+    for pkg in pkgs {
+        if !res.resolved.contains(&pkg.name) {
+            deep_deps(pkg, pkglist, &mut res);
+        }
+    }
 
-    vpr!("Resolved dependencies: {:?}", order);
-    let deps = order.iter().map(|d| Package::new(d, pkglist.clone())).collect::<Vec<Package>>();
-    deps
+    vpr!("Resolved dependencies: {:?}", res.order);
+    res.order.iter().map(|d| Package::new(d, pkglist.to_vec())).collect::<Vec<Package>>()
 }
 
-pub fn find_dependants(pkg: &Package, pkglist: Vec<Package>) -> Vec<Package> {
-    let mut dependants = pkglist
-        .iter()
-        .filter(|p| p.deps.contains(&pkg.name))
-        .inspect(|p| vpr!("Found dependant package: '{}'", p))
-        .cloned()
-        .collect::<Vec<Package>>();
+pub fn resolve_deps(pkg: &Package, pkglist: &[Package]) -> Vec<Package> {
+    resolve_all(std::slice::from_ref(pkg), pkglist)
+}
 
-    dependants.retain(|p| p.name != *pkg.name);
-    dedup(dependants)
+fn walk_paths(
+    name: &str,
+    target: &str,
+    pkglist: &[Package],
+    path: &mut Vec<String>,
+    dead: &mut HashSet<String>,
+    paths: &mut Vec<Vec<String>>,
+) -> bool {
+    // returns whether target is reachable from name, remembering dead ends
+    path.push(name.to_string());
+    if name == target {
+        paths.push(path.clone());
+        path.pop();
+        return true
+    }
+
+    let mut found = false;
+    let mut blocked = false; // a dead end is only certain if no dep was skipped for being on the path
+    let p = Package::new(name, pkglist.to_vec());
+    for dep in p.deps.iter() {
+        if dead.contains(dep) { continue }
+        if path.contains(dep) { blocked = true; continue }
+        found |= walk_paths(dep, target, pkglist, path, dead, paths);
+    }
+
+    if !found && !blocked { dead.insert(name.to_string()); }
+    path.pop();
+    found
+}
+
+pub fn dependency_paths(from: &Package, target: &str, pkglist: &[Package]) -> Vec<Vec<String>> {
+    // every simple dependency path from 'from' down to 'target'
+    let mut paths = Vec::new();
+    walk_paths(&from.name, target, pkglist, &mut Vec::new(), &mut HashSet::new(), &mut paths);
+
+    vpr!("Found {} paths from '{}' to '{}'", paths.len(), from.name, target);
+    paths
 }
 
-pub fn deep_dependants(deps: &Vec<Package>, pkglist: &[Package]) -> Vec<Package> {
+pub fn reverse_index(pkglist: &[Package]) -> HashMap<String, Vec<String>> {
+    // maps each package to the packages that directly depend on it
+    let mut index: HashMap<String, Vec<String>> = HashMap::new();
+    for p in pkglist {
+        for dep in p.deps.iter().filter(|d| **d != p.name) {
+            index.entry(dep.clone()).or_default().push(p.name.clone());
+        }
+    }
+
+    index.values_mut().for_each(|v| { v.sort(); v.dedup() });
+    index
+}
+
+pub fn reverse_deps(pkgs: &[Package], pkglist: &[Package], max_depth: usize) -> Vec<(Package, usize)> {
+    // transitive dependants of pkgs, each paired with its distance from them
+    let index = reverse_index(pkglist);
+    let mut seen: HashSet<String> = pkgs.iter().map(|p| p.name.clone()).collect();
+    let mut frontier: Vec<String> = pkgs.iter().map(|p| p.name.clone()).collect();
     let mut dependants = Vec::new();
-    for dep in deps {
-        let dep_dependants = find_dependants(dep, pkglist.to_vec());
-        dependants.extend(dep_dependants);
+
+    for depth in 1..=max_depth {
+        let next: Vec<String> = frontier
+            .iter()
+            .flat_map(|n| index.get(n).cloned().unwrap_or_default())
+            .filter(|d| seen.insert(d.clone()))
+            .collect();
+
+        if next.is_empty() { break }
+        vpr!("Found {} dependants at depth {}", next.len(), depth);
+
+        dependants.extend(next.iter().map(|d| (Package::new(d, pkglist.to_vec()), depth)));
+        frontier = next;
     }
+
+    dependants
+}
+
+pub fn deep_dependants(pkgs: &[Package], pkglist: &[Package]) -> Vec<Package> {
+    let dependants = reverse_deps(pkgs, pkglist, usize::MAX)
+        .into_iter()
+        .map(|(p, _)| p)
+        .collect();
     dedup(dependants)
 }
+
+pub fn installed_dependants(pkgs: &[Package], pkglist: &[Package]) -> Vec<Package> {
+    // the packages that would break if pkgs were removed
+    let mut dependants = deep_dependants(pkgs, pkglist);
+    dependants.retain(|p| matches!(p.status, PackageStatus::Installed));
+    dependants
+}
@@ -0,0 +1,157 @@
+// src/scaffold.rs
+//
+// responsible for scaffolding metafiles from tarballs
+
+use crate::cmd::static_exec;
+use crate::core::dl;
+use crate::flags::FORCE;
+use crate::paths::{META, REPO, SOURCES};
+use crate::utils::remove_before_first_number as rbfn;
+use crate::{die, msg, vpr};
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const EXTENSIONS: [&str; 9] = [
+    ".tar.gz", ".tar.xz", ".tar.bz2", ".tar.zst", ".tar.lz", ".tgz", ".txz", ".tbz2", ".tar",
+];
+
+#[derive(Debug)]
+enum BuildSystem {
+    Meson,
+    CMake,
+    Autotools,
+    Autoreconf,
+    Cargo,
+    Python,
+    Unknown,
+}
+
+impl BuildSystem {
+    fn idir(&self) -> &'static str {
+        match self {
+            BuildSystem::Meson      => "mn",
+            BuildSystem::CMake      => "CN",
+            BuildSystem::Autotools  => "cm",
+            BuildSystem::Autoreconf => "autoreconf -fiv &&\ncm",
+            BuildSystem::Cargo      => "cargo build --release &&\ninstall -vDm755 target/release/$NAME \"$RIDSTAGE/usr/bin/$NAME\"",
+            BuildSystem::Python     => "pip3 wheel -w dist --no-build-isolation --no-deps --no-cache-dir $PWD &&\npip3 install --root=\"$RIDSTAGE\" --no-index --find-links=dist --no-user $NAME",
+            BuildSystem::Unknown    => "die \"Build system not detected\"",
+        }
+    }
+}
+
+fn guess_version(name: &str, file_name: &str) -> String {
+    let stem = EXTENSIONS
+        .iter()
+        .find_map(|ext| file_name.strip_suffix(ext))
+        .unwrap_or(file_name);
+
+    let stem = stem.strip_prefix(name).unwrap_or(stem);
+    rbfn(stem).to_string()
+}
+
+fn guess_upstream(url: &str) -> String {
+    let hosts = ["github.com", "gitlab.com", "codeberg.org", "gitlab.freedesktop.org", "gitlab.gnome.org"];
+
+    let stripped = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")).unwrap_or(url);
+    let parts: Vec<&str> = stripped.split('/').collect();
+
+    match parts.as_slice() {
+        [host, owner, repo, ..] if hosts.contains(host) => format!("https://{}/{}/{}.git", host, owner, repo),
+        _ => String::new(),
+    }
+}
+
+fn detect(tarball: &Path) -> BuildSystem {
+    let listing = match static_exec(&format!("tar tf '{}'", tarball.display())) {
+        Ok(l) => l,
+        Err(e) => die!("Failed to list tarball contents: {}", e),
+    };
+
+    // only consider files directly under the top-level directory
+    let top: Vec<&str> = listing
+        .lines()
+        .filter_map(|l| {
+            let l = l.trim_start_matches("./");
+            let mut parts = l.splitn(2, '/');
+            parts.next();
+            parts.next()
+        })
+        .filter(|f| !f.is_empty() && !f.contains('/'))
+        .collect();
+
+    vpr!("Top-level files: {:?}", top);
+    let has = |f: &str| top.contains(&f);
+
+    match () {
+        _ if has("meson.build")                          => BuildSystem::Meson,
+        _ if has("CMakeLists.txt")                       => BuildSystem::CMake,
+        _ if has("configure")                            => BuildSystem::Autotools,
+        _ if has("configure.ac")                         => BuildSystem::Autoreconf,
+        _ if has("Cargo.toml")                           => BuildSystem::Cargo,
+        _ if has("pyproject.toml") || has("setup.py")    => BuildSystem::Python,
+        _ => BuildSystem::Unknown,
+    }
+}
+
+pub fn new(name: &str, url: &str) {
+    let metafile = META.join(&*REPO).join(name);
+    if metafile.exists() && !*FORCE.lock().unwrap() {
+        die!("Metafile '{}' already exists", metafile.display())
+    }
+
+    let file_name = url.split('/').next_back().unwrap_or_default();
+    let version = guess_version(name, file_name);
+    if version.is_empty() { die!("Could not guess a version from '{}'", file_name) }
+    vpr!("Guessed version '{}'", version);
+
+    let tarball = format!("{}-{}.tar", name, version);
+    let tarball_path = SOURCES.join(&tarball);
+    if !tarball_path.exists()
+        && let Err(e) = dl(url, &tarball, &tarball_path)
+    {
+        die!("{}", e)
+    }
+
+    let build_system = detect(&tarball_path);
+    msg!("Detected build system: {:?}", build_system);
+
+    let link = url.replace(&version, "$VERS");
+    let upstream = guess_upstream(url);
+    let idir = build_system.idir();
+
+    let contents = format!(r#"#!/bin/bash
+NAME="{name}"
+VERS="{version}"
+LINK="{link}"
+UPST="{upstream}"
+DEPS=""
+
+
+idir() {{
+
+{idir}
+
+}}
+
+rdir() {{
+
+echo "not implemented"
+
+}}
+"#);
+
+    let write = || -> std::io::Result<()> {
+        let mut f = File::create(&metafile)?;
+        f.write_all(contents.as_bytes())?;
+        fs::set_permissions(&metafile, fs::Permissions::from_mode(0o755))
+    };
+
+    if let Err(e) = write() {
+        die!("Failed to write metafile '{}': {}", metafile.display(), e)
+    }
+
+    msg!("Wrote metafile '{}'", metafile.display());
+}
@@ -4,6 +4,7 @@
 
 use crate::package::Package;
 use crate::paths::SETS;
+use crate::resolve::BOOTSTRAP;
 use crate::{die, erm, vpr};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -42,9 +43,17 @@ pub fn expand_set(set: &str, pkglist: &Vec<Package>) -> Vec<String> {
 
                 if is_set(&pk) {
                     all_packages.extend(expand_set(&pk, pkglist));
-                } else {
-                    all_packages.push(pk);
+                    continue
                 }
+
+                // 'freetype ~harfbuzz' builds freetype without harfbuzz first
+                let mut tokens = pk.split_whitespace();
+                let name = tokens.next().unwrap_or_default().to_string();
+                for dep in tokens.filter_map(|t| t.strip_prefix('~')) {
+                    vpr!("Registered bootstrap dependency '{}' for '{}'", dep, name);
+                    BOOTSTRAP.lock().unwrap().insert((name.clone(), dep.to_string()));
+                }
+                all_packages.push(name);
             }
             Err(e) => {
                 erm!("Failed to read set: {}", e);
@@ -0,0 +1,90 @@
+// src/signals.rs
+//
+// responsible for forwarding signals to builds and exiting cleanly
+
+use crate::erm;
+use std::process::exit;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+// the handler may only touch atomics and async-signal-safe calls
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+static DEFERRED: AtomicUsize = AtomicUsize::new(0);
+static GROUPS: [AtomicI32; 64] = [const { AtomicI32::new(0) }; 64];
+
+extern "C" fn handle(sig: libc::c_int) {
+    SIGNAL.store(sig, Ordering::SeqCst);
+
+    let mut forwarded = false;
+    for g in GROUPS.iter() {
+        let pgid = g.load(Ordering::SeqCst);
+        if pgid > 0 {
+            unsafe { libc::kill(-pgid, sig) };
+            forwarded = true;
+        }
+    }
+
+    // with nothing to clean up, exit right away
+    if !forwarded && DEFERRED.load(Ordering::SeqCst) == 0 {
+        unsafe { libc::_exit(128 + sig) }
+    }
+}
+
+pub fn install() {
+    for sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(sig, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+pub fn interrupted() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        sig => Some(sig),
+    }
+}
+
+pub fn exit_if_interrupted() {
+    if let Some(sig) = interrupted() {
+        erm!("Interrupted by signal {}", sig);
+        exit(128 + sig)
+    }
+}
+
+pub struct Group(usize);
+
+impl Group {
+    pub fn register(pgid: u32) -> Option<Group> {
+        // signals received while registered are forwarded to the process group,
+        // as is one that arrived before registering
+        let group = GROUPS.iter().position(|g| g.compare_exchange(0, pgid as i32, Ordering::SeqCst, Ordering::SeqCst).is_ok()).map(Group);
+        if let Some(sig) = interrupted() { unsafe { libc::kill(-(pgid as i32), sig) }; }
+        group
+    }
+}
+
+impl Drop for Group {
+    fn drop(&mut self) {
+        GROUPS[self.0].store(0, Ordering::SeqCst);
+    }
+}
+
+pub struct Defer;
+
+impl Defer {
+    pub fn new() -> Defer {
+        // while held, signals set the interrupted flag instead of exiting
+        DEFERRED.fetch_add(1, Ordering::SeqCst);
+        Defer
+    }
+}
+
+impl Drop for Defer {
+    fn drop(&mut self) {
+        DEFERRED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
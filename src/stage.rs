@@ -0,0 +1,217 @@
+// src/stage.rs
+//
+// responsible for inspecting staged installs, merging them into the live system,
+// and keeping track of the files each package installed
+
+use crate::paths::{FILES, TRASH};
+use crate::triggers;
+use crate::utils::mkdir;
+use crate::{erm, vpr};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+// packages should never install here
+const FORBIDDEN: [&str; 7] = ["dev", "home", "proc", "root", "run", "sys", "tmp"];
+
+fn collect(dir: &Path, stage: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect(&path, stage, files)?;
+        } else {
+            files.push(Path::new("/").join(path.strip_prefix(stage).unwrap()));
+        }
+    }
+    Ok(())
+}
+
+fn discard(stage: &Path) -> Result<(), String> {
+    // some configs send docs to $RIDTRASH (XORG_CONFIG's --docdir), which is never merged
+    let trash = stage.join(TRASH.strip_prefix("/").unwrap_or(&TRASH));
+    if !trash.exists() { return Ok(()) }
+    fs::remove_dir_all(&trash).map_err(|e| format!("Failed to discard '{}': {}", trash.display(), e))?;
+
+    // along with any directories left empty above it
+    let mut dir = trash.parent();
+    while let Some(d) = dir.filter(|d| *d != stage) {
+        if fs::remove_dir(d).is_err() { break }
+        dir = d.parent();
+    }
+    Ok(())
+}
+
+pub fn inspect(name: &str, stage: &Path) -> Result<Vec<PathBuf>, String> {
+    // returns the staged files as they'll appear on the live system
+    discard(stage)?;
+    let mut files = Vec::new();
+    if stage.exists() {
+        collect(stage, stage, &mut files).map_err(|e| format!("Failed to read '{}': {}", stage.display(), e))?;
+    }
+    files.sort();
+
+    if files.is_empty() {
+        erm!("Nothing was staged for '{}'; if it installs straight to /, set STAG=\"no\"", name);
+        return Ok(files)
+    }
+
+    // usually means DESTDIR was passed to configure as well as to make install
+    let doubled = stage.join(stage.strip_prefix("/").unwrap_or(stage));
+    if doubled.exists() {
+        return Err(format!("Files were staged under '{}'", doubled.display()))
+    }
+
+    if let Some(f) = files.iter().find(|f| {
+        f.components().nth(1).is_some_and(|c| FORBIDDEN.contains(&&*c.as_os_str().to_string_lossy()))
+    }) {
+        return Err(format!("Refusing to install '{}'", f.display()))
+    }
+
+    let owners = owners();
+    for f in files.iter() {
+        if let Some(o) = owners.get(f).and_then(|o| o.iter().find(|o| *o != name)) {
+            erm!("'{}' from '{}' is already owned by '{}'", f.display(), name, o)
+        }
+    }
+
+    vpr!("Staged {} files for '{}'", files.len(), name);
+    Ok(files)
+}
+
+fn install(src: &Path, dest: &Path, symlinked: bool) -> io::Result<()> {
+    // files are written beside their destination and renamed over it, so the
+    // live path always points at either the old or the new file
+    if dest.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+        return Err(io::Error::other(format!("'{}' is a directory", dest.display())))
+    }
+
+    let name = dest.file_name().unwrap().to_string_lossy();
+    let tmp = dest.with_file_name(format!(".{}.rid-new", name));
+    let _ = fs::remove_file(&tmp);
+
+    if symlinked {
+        symlink(fs::read_link(src)?, &tmp)?;
+    } else {
+        fs::copy(src, &tmp)?;
+    }
+
+    fs::rename(&tmp, dest).inspect_err(|_| { let _ = fs::remove_file(&tmp); })
+}
+
+fn walk(from: &Path, to: &Path, merged: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let src = entry.path();
+        let dest = to.join(entry.file_name());
+        let kind = entry.file_type()?;
+
+        if kind.is_dir() {
+            // existing directories (or symlinks to them, like /lib) are kept as they are
+            if !dest.is_dir() {
+                if dest.symlink_metadata().is_ok() { fs::remove_file(&dest)? }
+                fs::create_dir(&dest)?;
+                fs::set_permissions(&dest, fs::metadata(&src)?.permissions())?;
+            }
+            walk(&src, &dest, merged)?;
+            continue
+        }
+
+        install(&src, &dest, kind.is_symlink())
+            .map_err(|e| io::Error::other(format!("Failed to merge '{}': {}", dest.display(), e)))?;
+
+        vpr!("Merged '{}'", dest.display());
+        merged.push(dest);
+    }
+    Ok(())
+}
+
+pub fn merge(stage: &Path, root: &Path, merged: &mut Vec<PathBuf>) -> io::Result<()> {
+    // merged collects every file that reached the live system, even if a later one failed
+    if stage.exists() {
+        walk(stage, root, merged)?;
+    }
+    merged.sort();
+    Ok(())
+}
+
+pub fn manifest(name: &str) -> Vec<PathBuf> {
+    // $RIDHOME/files/<name>, one path per line
+    fs::read_to_string(FILES.join(name))
+        .map(|s| s.lines().map(PathBuf::from).collect())
+        .unwrap_or_default()
+}
+
+fn owners() -> HashMap<PathBuf, Vec<String>> {
+    let mut owners: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let Ok(entries) = fs::read_dir(&*FILES) else { return owners };
+    for e in entries.filter_map(Result::ok) {
+        let name = e.file_name().to_string_lossy().to_string();
+        for f in manifest(&name) {
+            owners.entry(f).or_default().push(name.clone());
+        }
+    }
+    owners
+}
+
+fn unlink(name: &str, files: &[&PathBuf]) {
+    // files another package also installed are left alone, as are directories
+    let owners = owners();
+    for f in files {
+        if owners.get(*f).is_some_and(|o| o.iter().any(|o| o != name)) { continue }
+        if f.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+            match fs::remove_file(f) {
+                Ok(_) => {
+                    vpr!("Removed '{}'", f.display());
+                    triggers::touch(&[f.to_path_buf()]);
+                }
+                Err(e) => erm!("Failed to remove '{}': {}", f.display(), e),
+            }
+        }
+    }
+}
+
+fn write(name: &str, files: &[PathBuf]) {
+    mkdir(&FILES);
+    let contents: String = files.iter().map(|f| format!("{}\n", f.display())).collect();
+    if let Err(e) = fs::write(FILES.join(name), contents) {
+        erm!("Failed to record files for '{}': {}", name, e)
+    }
+}
+
+pub fn record(name: &str, files: &[PathBuf]) {
+    // files the previous build installed but this one didn't are removed
+    let new: HashSet<&PathBuf> = files.iter().collect();
+    let old = manifest(name);
+    let stale: Vec<&PathBuf> = old.iter().filter(|f| !new.contains(f)).collect();
+    unlink(name, &stale);
+    triggers::touch(files);
+    write(name, files);
+}
+
+pub fn claim(name: &str, files: &[PathBuf]) {
+    // after a partial merge, the merged files are added to the old list, and nothing is removed
+    let mut all = manifest(name);
+    all.extend(files.iter().cloned());
+    triggers::touch(files);
+    all.sort();
+    all.dedup();
+    write(name, &all);
+}
+
+pub fn forget(name: &str) {
+    // removes a package's recorded files from the live system
+    let path = FILES.join(name);
+    if !path.exists() {
+        triggers::untracked();
+        return
+    }
+
+    let files = manifest(name);
+    unlink(name, &files.iter().collect::<Vec<_>>());
+    if let Err(e) = fs::remove_file(&path) {
+        erm!("Failed to remove '{}': {}", path.display(), e)
+    }
+}
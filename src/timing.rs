@@ -0,0 +1,84 @@
+// src/timing.rs
+//
+// responsible for build timing, sbu calibration, and time estimates
+
+use crate::package::{BuildOutcome, Package};
+use crate::erm;
+use crate::paths::SBU;
+use std::fmt;
+use std::fs;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing {
+    pub wall: Duration,
+    pub cpu: Duration,
+}
+
+pub fn human(d: Duration) -> String {
+    let s = d.as_secs();
+    match (s / 3600, s % 3600 / 60, s % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m{:02}s", m, s),
+        (h, m, _) => format!("{}h{:02}m", h, m),
+    }
+}
+
+pub fn sbu() -> Option<Duration> {
+    // one sbu, in seconds, as stored by --calibrate
+    let s = fs::read_to_string(&*SBU).ok()?;
+    s.trim().parse::<f64>().ok().filter(|s| *s > 0.0).map(Duration::from_secs_f64)
+}
+
+pub fn save_sbu(d: Duration) {
+    if let Err(e) = fs::write(&*SBU, format!("{}\n", d.as_secs_f64())) {
+        erm!("Failed to save sbu to '{}': {}", SBU.display(), e)
+    }
+}
+
+pub struct Estimate {
+    pub sbus: Option<f64>,
+    pub time: Option<Duration>,
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.sbus, self.time) {
+            (Some(s), Some(t)) => write!(f, "≈ {} SBU ≈ {}", round(s), human(t)),
+            (Some(s), None) => write!(f, "≈ {} SBU", round(s)),
+            (None, Some(t)) => write!(f, "≈ {}", human(t)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+fn round(sbus: f64) -> String {
+    if sbus < 10.0 { format!("{:.1}", sbus) } else { format!("{:.0}", sbus) }
+}
+
+pub fn estimate(pkg: &Package) -> Option<Estimate> {
+    // past successful builds take precedence over the metafile hint
+    let sbu = sbu();
+    let past = pkg.build
+        .as_ref()
+        .filter(|b| b.outcome == BuildOutcome::Succeeded && b.wall > 0.0)
+        .map(|b| Duration::from_secs_f64(b.wall));
+
+    match (past, pkg.sbus, sbu) {
+        (Some(t), _, Some(u)) => Some(Estimate { sbus: Some(t.as_secs_f64() / u.as_secs_f64()), time: Some(t) }),
+        (Some(t), _, None) => Some(Estimate { sbus: None, time: Some(t) }),
+        (None, Some(s), Some(u)) => Some(Estimate { sbus: Some(s), time: Some(u.mul_f64(s)) }),
+        (None, Some(s), None) => Some(Estimate { sbus: Some(s), time: None }),
+        (None, None, _) => None,
+    }
+}
+
+pub fn total(pkgs: &[&Package]) -> Option<Duration> {
+    // only counts packages with a time estimate
+    let times: Vec<Duration> = pkgs.iter().filter_map(|p| estimate(p)?.time).collect();
+    if times.is_empty() { None } else { Some(times.iter().sum()) }
+}
+
+pub fn annotate(pkg: &Package) -> String {
+    estimate(pkg).map_or(String::new(), |e| format!(" ({})", e))
+}
@@ -3,17 +3,18 @@
 // responsible for keeping track of packages
 
 use crate::checks::is_file_empty;
-use crate::package::{Package, PackageStatus};
-use crate::paths::{FAILED, META, PKGSJSON, REPO};
-use crate::utils::{get_mod_time, form_cache_list};
-use crate::{die, vpr};
+use crate::events;
+use crate::flags::PRETEND;
+use crate::package::{BuildRecord, Package, PackageStatus};
+use crate::paths::{META, PKGSJSON, REPO};
+use crate::signals::Defer;
+use crate::utils::{display_list, form_cache_list};
+use crate::{die, erm, vpr, yn};
 use indicatif::{ProgressBar, ProgressStyle};
-use serde_json::{from_str, to_string_pretty};
+use serde_json::{from_str, json, to_string_pretty};
 use std::collections::HashSet;
 use std::fs::{read_to_string, File};
 use std::io::{self, Write};
-use std::path::Path;
-use std::time::UNIX_EPOCH;
 
 pub fn create_json() -> io::Result<()> {
     if !is_file_empty(&PKGSJSON) { return Ok(()) }
@@ -31,23 +32,37 @@ pub fn load_pkglist() -> Vec<Package> {
 }
 
 pub fn save_pkglist(pkg_list: &Vec<Package>) {
+    if *PRETEND.lock().unwrap() {
+        vpr!("Not saving $RIDPKGSJSON while pretending");
+        return
+    }
+
+    let _defer = Defer::new();
     let jsdata = to_string_pretty(pkg_list).expect("Failed to serialize package data");
     let mut file = File::create(&*PKGSJSON).expect("Failed to create $RIDPKGSJSON");
     file.write_all(jsdata.as_bytes())
         .expect("Failed to write to $RIDPKGSJSON");
 }
 
-fn build_failed() -> bool {
-    Path::new(&*FAILED).exists()
-}
+pub fn record(pkglist: &mut Vec<Package>, p: &Package, record: BuildRecord) {
+    // records the outcome of the latest build of a package
+    if let Some(package) = pkglist.iter_mut().find(|pkg| pkg.name == p.name) {
+        vpr!("Recording {:?} build for '{}'", record.outcome, package);
+        events::emit("db_update", json!({ "package": package.name, "change": "build_record", "outcome": record.outcome }));
+        package.build = Some(record);
+    }
 
-pub fn add(pkglist: &mut Vec<Package>, p: &Package) {
-    if build_failed() { die!("Build failed") }
+    save_pkglist(pkglist);
+}
 
+pub fn add(pkglist: &mut Vec<Package>, p: &Package, explicit: bool) {
+    // explicit marks packages that were requested rather than pulled in as dependencies
     if let Some(package) = pkglist.iter_mut().find(|pkg| pkg.name == p.name) {
         vpr!("Adding package: '{}'", package);
         package.status = PackageStatus::Installed;
         package.installed_version = package.version.clone();
+        package.explicit |= explicit;
+        events::emit("db_update", json!({ "package": package.name, "change": "installed", "version": package.version, "explicit": package.explicit }));
     }
 
     save_pkglist(pkglist);
@@ -57,6 +72,8 @@ pub fn rem(pkglist: &mut Vec<Package>, p: &Package) {
     if let Some(package) = pkglist.iter_mut().find(|pkg| pkg.name == p.name) {
         package.status = PackageStatus::Available;
         package.installed_version = "".to_string();
+        package.explicit = false;
+        events::emit("db_update", json!({ "package": package.name, "change": "removed" }));
         save_pkglist(pkglist);
         return
     }
@@ -64,21 +81,53 @@ pub fn rem(pkglist: &mut Vec<Package>, p: &Package) {
     die!("Package '{}' not found", p)
 }
 
+fn handle_orphans(pkglist: &mut Vec<Package>, ignored: &HashSet<String>) {
+    // detects entries whose metafiles were deleted from the repo
+    let repo = META.join(&*REPO);
+    let mut changed = false;
+
+    for p in pkglist.iter_mut().filter(|p| p.orphaned) {
+        if repo.join(&p.name).exists() {
+            vpr!("Metafile for '{}' reappeared", p.name);
+            p.orphaned = false;
+            changed = true;
+        }
+    }
+
+    let orphans: Vec<Package> = pkglist
+        .iter()
+        .filter(|p| !p.orphaned && !ignored.contains(&p.name) && !repo.join(&p.name).exists())
+        .cloned()
+        .collect();
+
+    if !orphans.is_empty() {
+        erm!("Found {} entries whose metafiles were deleted:", orphans.len());
+        display_list(&orphans);
+
+        if yn!("Drop these entries? (otherwise they are flagged as orphaned)", false) {
+            pkglist.retain(|p| !orphans.contains(p));
+            vpr!("Dropped {} orphaned entries", orphans.len());
+        } else {
+            pkglist.iter_mut().filter(|p| orphans.contains(p)).for_each(|p| p.orphaned = true);
+            vpr!("Flagged {} orphaned entries", orphans.len());
+        }
+        changed = true;
+    }
+
+    if changed { save_pkglist(pkglist) }
+}
+
 const TEMPLATE: &str = "{msg:.red} [{elapsed_precise}] [{wide_bar:.red/black}] {pos}/{len} ({eta})";
 pub fn cache_changes(forcibly:bool, pkglist: &mut Vec<Package>, mut cache_list: Vec<String>) -> io::Result<u64> {
     // caches changes made in $RIDMETA to $RIDPKGSJSON
-    let json_mod_time = if forcibly {
-        UNIX_EPOCH
-    } else {
-        get_mod_time(&PKGSJSON)?
-    };
-
     let ignored: HashSet<String> = ["README.md", "LICENSE", ".git"]
         .iter()
         .map(|&s| s.to_string())
         .collect();
 
-    if cache_list.is_empty() { form_cache_list(forcibly, &META.join(&*REPO), json_mod_time, &mut cache_list, &ignored)? }
+    handle_orphans(pkglist, &ignored);
+
+    if cache_list.is_empty() { form_cache_list(forcibly, &META.join(&*REPO), pkglist, &mut cache_list, &ignored)? }
     if cache_list.is_empty() { vpr!("Empty cache list"); return Ok(0) }
 
     let length = cache_list.len() as u64;
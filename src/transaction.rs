@@ -0,0 +1,97 @@
+// src/transaction.rs
+//
+// responsible for persisting in-progress plans so they can be resumed
+
+use crate::flags::PRETEND;
+use crate::package::Package;
+use crate::paths::TRANSACTION;
+use crate::plan::{Action, Plan, Step};
+use crate::signals::Defer;
+use crate::{die, erm, vpr};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string_pretty};
+use std::fs;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub action: Action,
+    pub done: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Transaction {
+    pub explicit: Vec<String>,
+    pub steps: Vec<Entry>,
+}
+
+impl Transaction {
+    pub fn remaining(&self) -> impl Iterator<Item = &Entry> {
+        self.steps.iter().filter(|e| !e.done)
+    }
+
+    pub fn plan(&self, pkglist: &[Package]) -> Plan {
+        // packages are re-formed so fixed metafiles are picked up
+        let steps = self.remaining()
+            .map(|e| Step { pkg: Package::new(&e.name, pkglist.to_vec()), action: e.action.clone() })
+            .collect();
+
+        Plan::resolved(steps)
+    }
+}
+
+fn save(t: &Transaction) {
+    let _defer = Defer::new();
+    let result = to_string_pretty(t)
+        .map_err(|e| e.to_string())
+        .and_then(|s| fs::write(&*TRANSACTION, s).map_err(|e| e.to_string()));
+
+    if let Err(e) = result {
+        erm!("Failed to save transaction to '{}': {}", TRANSACTION.display(), e)
+    }
+}
+
+pub fn load() -> Option<Transaction> {
+    let contents = fs::read_to_string(&*TRANSACTION).ok()?;
+    match from_str(&contents) {
+        Ok(t) => Some(t),
+        Err(e) => die!("Failed to parse '{}': {}", TRANSACTION.display(), e),
+    }
+}
+
+pub fn begin(plan: &Plan, explicit: &[Package]) {
+    if *PRETEND.lock().unwrap() { return }
+
+    let t = Transaction {
+        explicit: explicit.iter().map(|p| p.name.clone()).collect(),
+        steps: plan.steps
+            .iter()
+            .map(|s| Entry {
+                name: s.pkg.name.clone(),
+                action: s.action.clone(),
+                done: matches!(s.action, Action::Skip | Action::Hold),
+            })
+            .collect(),
+    };
+
+    vpr!("Saving transaction of {} steps", t.steps.len());
+    save(&t);
+}
+
+pub fn done(index: usize) {
+    // marks plan.steps[index] as done
+    if *PRETEND.lock().unwrap() { return }
+    let Some(mut t) = load() else { return };
+
+    if let Some(e) = t.steps.get_mut(index) {
+        e.done = true;
+        save(&t);
+    }
+}
+
+pub fn clear() {
+    if !TRANSACTION.exists() { return }
+    if let Err(e) = fs::remove_file(&*TRANSACTION) {
+        erm!("Failed to remove '{}': {}", TRANSACTION.display(), e)
+    }
+}
@@ -0,0 +1,94 @@
+// src/triggers.rs
+//
+// responsible for running path triggers once at the end of a transaction
+
+use crate::cmd::exec;
+use crate::log;
+use crate::paths::TRIGGERS;
+use crate::{erm, msg, vpr};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+lazy_static! {
+    // paths merged or removed during this transaction
+    static ref TOUCHED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+    // set when something changed the live system without a file list
+    static ref UNTRACKED: Mutex<bool> = Mutex::new(false);
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Trigger {
+    #[serde(skip)]
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+    pub run: String,
+}
+
+fn load() -> Vec<Trigger> {
+    // $RIDHOME/triggers/<name>.toml
+    let Ok(entries) = fs::read_dir(&*TRIGGERS) else { return Vec::new() };
+    let mut paths: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let trigger = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| toml::from_str::<Trigger>(&s).map_err(|e| e.to_string()));
+
+            match trigger {
+                Ok(t) => Some(Trigger { name, ..t }),
+                Err(e) => {
+                    erm!("Ignoring trigger '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn touch(files: &[PathBuf]) {
+    TOUCHED.lock().unwrap().extend(files.iter().cloned());
+}
+
+pub fn untracked() {
+    // unstaged installs and unrecorded removals could have touched anything
+    *UNTRACKED.lock().unwrap() = true;
+}
+
+pub fn reset() {
+    TOUCHED.lock().unwrap().clear();
+    *UNTRACKED.lock().unwrap() = false;
+}
+
+pub fn run() {
+    let touched = std::mem::take(&mut *TOUCHED.lock().unwrap());
+    let untracked = std::mem::take(&mut *UNTRACKED.lock().unwrap());
+    let fired: Vec<Trigger> = load()
+        .into_iter()
+        .filter(|t| untracked || touched.iter().any(|f| t.paths.iter().any(|p| f.starts_with(p))))
+        .collect();
+    if fired.is_empty() { return }
+
+    let log = log::create("triggers", "triggers", "path triggers");
+    for t in fired.iter() {
+        msg!("Running trigger '{}'", t.name);
+        log::append(&log, &format!("# trigger: {}", t.name));
+        match exec(&t.run, &log, None) {
+            Ok((status, _)) if status.success() => vpr!("Trigger '{}' succeeded", t.name),
+            Ok((status, _)) => erm!("Trigger '{}' failed: {}", t.name, status),
+            Err(e) => erm!("Trigger '{}' failed: {}", t.name, e),
+        }
+    }
+    log::prune("triggers");
+}
@@ -3,14 +3,12 @@
 // responsible for defining utility functions
 
 use crate::config::CONFIG;
-use crate::flags::FORCE;
-use crate::package::{Package, PackageStatus};
-use crate::{die, vpr, msg, erm};
+use crate::package::{BuildOutcome, Package};
+use crate::{die, vpr, erm};
 use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::path::Path;
-use std::time::SystemTime;
 use strsim::levenshtein;
 
 pub fn mkdir(path: &Path) {
@@ -25,11 +23,14 @@ pub fn mkdir(path: &Path) {
     vpr!("Created directory '{}'", path.display());
 }
 
-pub fn get_mod_time(path: &Path) -> io::Result<SystemTime> {
-    let metadata = fs::metadata(path)?;
-    metadata
-        .modified()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    // 64-bit FNV-1a, which is stable across rust versions unlike DefaultHasher
+    let contents = fs::read(path)?;
+    let hash = contents.iter().fold(0xcbf29ce484222325_u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    });
+
+    Ok(format!("{:016x}", hash))
 }
 
 pub fn format_line(line: &str, max_length: usize) -> String {
@@ -64,6 +65,16 @@ pub fn display_list(list: &[Package] ) {
             iv = format!("{}\x1b[31;1m (outdated)", p.installed_version)
         }
 
+        match p.build.as_ref().map(|b| &b.outcome) {
+            Some(BuildOutcome::Failed) => iv = format!("{}\x1b[31;1m (failed)", iv),
+            Some(BuildOutcome::Interrupted) => iv = format!("{}\x1b[31;1m (interrupted)", iv),
+            _ => (),
+        }
+
+        if p.orphaned {
+            iv = format!("{}\x1b[33;1m (orphaned)", iv)
+        }
+
         let line = format!(
             "{}={} ~ {:?} {}",
             p.name, p.version, p.status, iv
@@ -81,20 +92,7 @@ pub fn dedup(mut vec: Vec<Package>) -> Vec<Package> {
     vec
 }
 
-pub fn do_install(p: &Package) -> bool {
-    match p.status {
-        PackageStatus::Installed => {
-            msg!("{} is already installed", p);
-            *FORCE.lock().unwrap()
-        }
-        _ => {
-            msg!("Installing {}", p);
-            true
-        }
-    }
-}
-
-pub fn form_cache_list(forcibly: bool, path: &Path, json_mod_time: SystemTime, cache_list: &mut Vec<String>, ignored: &HashSet<String>) -> io::Result<()> {
+pub fn form_cache_list(forcibly: bool, path: &Path, pkglist: &[Package], cache_list: &mut Vec<String>, ignored: &HashSet<String>) -> io::Result<()> {
     for entry in fs::read_dir(path)? {
         let entry = entry?;
         let path = entry.path();
@@ -104,9 +102,12 @@ pub fn form_cache_list(forcibly: bool, path: &Path, json_mod_time: SystemTime, c
         }
 
         if let Some(pkg_str) = path.file_name().and_then(|n| n.to_str()) {
-            let entry_mod_time = get_mod_time(&path)?;
+            let changed = forcibly || match pkglist.iter().find(|p| p.name == pkg_str) {
+                Some(p) => p.hash != hash_file(&path)?,
+                None => true,
+            };
 
-            if entry_mod_time >= json_mod_time || forcibly {
+            if changed {
                 vpr!("Caching package '{}'...", pkg_str);
 
                 let pkg_str = pkg_str.to_string(); // lazy expected type fix
@@ -119,6 +120,23 @@ pub fn form_cache_list(forcibly: bool, path: &Path, json_mod_time: SystemTime, c
     Ok(())
 }
 
+pub fn glob(pattern: &str, s: &str) -> bool {
+    // matches '*' (any run) and '?' (any one character)
+    fn inner(p: &[char], s: &[char]) -> bool {
+        match (p.first(), s.first()) {
+            (None, None) => true,
+            (Some('*'), _) => inner(&p[1..], s) || (!s.is_empty() && inner(p, &s[1..])),
+            (Some('?'), Some(_)) => inner(&p[1..], &s[1..]),
+            (Some(a), Some(b)) if a == b => inner(&p[1..], &s[1..]),
+            _ => false,
+        }
+    }
+
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    inner(&p, &s)
+}
+
 pub fn remove_before_first_number(s: &str) -> &str {
     s.find(|c: char| c.is_ascii_digit())
         .map_or("", |index| &s[index..])